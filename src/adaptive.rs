@@ -0,0 +1,57 @@
+/** Running mean and variance of the luminance of a pixel's samples (Welford's algorithm),
+ * telling adaptive sampling when the pixel has enough of them */
+#[derive(Clone, Copy, Default)]
+pub struct PixelStats {
+    samples: u64,
+    mean: f32,
+    m2: f32
+}
+
+impl PixelStats {
+    /* Luminance below which errors are measured in absolute rather than relative terms,
+     * otherwise nearly black pixels would never be done */
+    const MIN_LUMINANCE: f32 = 0.01;
+
+    pub fn new() -> Self {
+        PixelStats {
+            samples: 0,
+            mean: 0.0,
+            m2: 0.0
+        }
+    }
+
+    /** Sample count, mean and sum of squared differences from the mean, to store the statistics */
+    pub fn state(&self) -> (u64, f32, f32) {
+        (self.samples, self.mean, self.m2)
+    }
+
+    pub fn from_state(samples: u64, mean: f32, m2: f32) -> Self {
+        PixelStats {
+            samples, mean, m2
+        }
+    }
+
+    pub fn add(&mut self, luminance: f32) {
+        if !luminance.is_finite() {
+            return;
+        }
+        self.samples += 1;
+        let delta = luminance - self.mean;
+        self.mean += delta / self.samples as f32;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    /** Standard error of the mean luminance, relative to the mean */
+    pub fn relative_error(&self) -> f32 {
+        if self.samples < 2 {
+            return f32::INFINITY;
+        }
+        let variance = self.m2 / (self.samples - 1) as f32;
+        return (variance / self.samples as f32).sqrt() / self.mean.max(PixelStats::MIN_LUMINANCE);
+    }
+
+    /** Whether the pixel has at least `min_samples` samples and an error below `threshold` */
+    pub fn converged(&self, min_samples: u64, threshold: f32) -> bool {
+        return self.samples >= min_samples && self.relative_error() <= threshold;
+    }
+}
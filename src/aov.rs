@@ -0,0 +1,141 @@
+use crate::{color::Color, ray::Ray, structures::{scene::Scene, renderable::HitRecord}};
+
+/** Arbitrary output variable, a render pass written next to the beauty image */
+#[derive(Clone, Copy, PartialEq)]
+pub enum Aov {
+    /** Distance from the camera to the first surface */
+    Depth,
+    /** World space normal of the first surface */
+    Normal,
+    /** Reflectance of the first surface */
+    Albedo,
+    /** World space position of the first surface */
+    Position,
+    /** Identifier of the node that was hit, 0 for the background */
+    NodeId,
+    /** Index of the material that was hit plus one, 0 for the background */
+    MaterialId,
+    /** Light seen by the camera directly or after a single bounce */
+    Direct,
+    /** Light that bounced more than once */
+    Indirect,
+    /** Variance of the pixel's luminance estimate, the noise left in the image */
+    Variance,
+    /** Number of samples taken in the pixel, a heatmap of adaptive sampling */
+    Samples
+}
+
+impl Aov {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "depth" => Some(Aov::Depth),
+            "normal" => Some(Aov::Normal),
+            "albedo" => Some(Aov::Albedo),
+            "position" => Some(Aov::Position),
+            "node" => Some(Aov::NodeId),
+            "material" => Some(Aov::MaterialId),
+            "direct" => Some(Aov::Direct),
+            "indirect" => Some(Aov::Indirect),
+            "variance" => Some(Aov::Variance),
+            "samples" => Some(Aov::Samples),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Position => "position",
+            Aov::NodeId => "node",
+            Aov::MaterialId => "material",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Variance => "variance",
+            Aov::Samples => "samples"
+        }
+    }
+
+    /** IDs can't be blended, they are taken from the first sample of a pixel
+     * while the other passes are averaged over all samples */
+    pub fn is_averaged(&self) -> bool {
+        !matches!(self, Aov::NodeId | Aov::MaterialId)
+    }
+
+    /** Turns the values summed over the samples of a pixel into the value of the pass */
+    pub fn finish(&self, sum: &Color, samples: u64) -> Color {
+        if !self.is_averaged() || samples == 0 {
+            return sum.copy();
+        }
+
+        let n = samples as f32;
+        return match self {
+            Aov::Variance => {
+                // Sample variance of the luminance, divided by the count for the variance of the mean
+                let mean = sum.r / n;
+                let v = if samples > 1 {
+                    ((sum.g - n * mean * mean) / (n - 1.0)).max(0.0) / n
+                } else {
+                    0.0
+                };
+                Color::new(v, v, v)
+            },
+            Aov::Samples => sum.copy(),
+            _ => Color::scale(sum, 1.0 / n)
+        };
+    }
+
+    /** Value of the pass for one camera sample. `hit` is the first surface along the
+     * camera ray, `direct` and `indirect` the light the integrator found. */
+    pub fn value(&self, scene: &Scene, ray: &Ray, hit: Option<&HitRecord>, direct: &Color, indirect: &Color) -> Color {
+        match self {
+            Aov::Direct => {
+                return direct.copy();
+            },
+            Aov::Indirect => {
+                return indirect.copy();
+            },
+            Aov::Variance => {
+                let l = Color::add(direct, indirect).luminance();
+                return Color::new(l, l * l, 0.0);
+            },
+            Aov::Samples => {
+                return Color::new(1.0, 1.0, 1.0);
+            },
+            _ => {}
+        }
+
+        let hit = match hit {
+            Some(h) => h,
+            None => {
+                return Color::new(0.0, 0.0, 0.0);
+            }
+        };
+
+        return match self {
+            Aov::Depth => {
+                let d = hit.t() * ray.get_direction().length();
+                Color::new(d, d, d)
+            },
+            Aov::Normal => {
+                let n = hit.n();
+                Color::new(n.x, n.y, n.z)
+            },
+            Aov::Albedo => scene.material(hit.material()).albedo(hit),
+            Aov::Position => {
+                let p = hit.p();
+                Color::new(p.x, p.y, p.z)
+            },
+            Aov::NodeId => {
+                let id = hit.node() as f32;
+                Color::new(id, id, id)
+            },
+            Aov::MaterialId => {
+                let id = (hit.material().index() + 1) as f32;
+                Color::new(id, id, id)
+            },
+            Aov::Direct | Aov::Indirect | Aov::Variance | Aov::Samples => Color::new(0.0, 0.0, 0.0)
+        };
+    }
+}
@@ -0,0 +1,291 @@
+use std::{fs, io::ErrorKind};
+
+use crate::{color::Color, adaptive::PixelStats, film::Film, filter::Filter};
+
+const MAGIC: &str = "RAYBOW CHECKPOINT 1";
+
+/** What a pixel gathered besides its share of the film: the number of samples taken, the
+ * statistics of adaptive sampling, the summed render passes and the cryptomatte coverage */
+#[derive(Default)]
+pub struct PixelState {
+    pub samples: u64,
+    pub stats: PixelStats,
+    pub aovs: Vec<Color>,
+    pub coverage: Vec<Vec<(f32, f32)>>
+}
+
+/** Everything a progressive render accumulated over its finished passes, enough to write
+ * the image or to resume the render. The random numbers of a sample only depend on the seed,
+ * the pixel and the sample index, so the number of samples taken is the state of the samplers. */
+pub struct RenderState {
+    /** Samples per pixel of the finished passes, pixels done early with adaptive sampling have fewer */
+    pub samples: u64,
+    pub film: Film,
+    pub pixels: Vec<PixelState>,
+    /** Light images of earlier runs, summed over their camera samples */
+    pub light: Vec<Color>
+}
+
+impl RenderState {
+    pub fn new(width: u64, height: u64, filter: Filter, aovs: usize, layers: usize) -> Self {
+        let pixels = (0..width * height).map(|_| PixelState {
+            samples: 0,
+            stats: PixelStats::new(),
+            aovs: (0..aovs).map(|_| Color::new(0.0, 0.0, 0.0)).collect(),
+            coverage: vec![Vec::new(); layers]
+        }).collect();
+
+        RenderState {
+            samples: 0,
+            film: Film::new(width, height, filter),
+            pixels,
+            light: Vec::new()
+        }
+    }
+
+    /** Number of camera samples taken in all pixels */
+    pub fn total_samples(&self) -> u64 {
+        self.pixels.iter().map(|p| p.samples).sum()
+    }
+
+    /** Writes the state to a file, along with a signature of the settings it was rendered with.
+     * `light` replaces the light images of earlier runs, it should include them. */
+    pub fn save(&self, filename: &str, signature: &str, light: Option<&[Color]>) -> Result<(), String> {
+        let mut out = format!("{}\n{}\n", MAGIC, signature).into_bytes();
+        write_u64(&mut out, self.samples);
+        self.film.encode(&mut out);
+
+        for p in self.pixels.iter() {
+            let (n, mean, m2) = p.stats.state();
+            write_u64(&mut out, p.samples);
+            write_u64(&mut out, n);
+            write_f32(&mut out, mean);
+            write_f32(&mut out, m2);
+            for c in p.aovs.iter() {
+                write_color(&mut out, c);
+            }
+            for layer in p.coverage.iter() {
+                write_u64(&mut out, layer.len() as u64);
+                for (id, amount) in layer.iter() {
+                    write_f32(&mut out, *id);
+                    write_f32(&mut out, *amount);
+                }
+            }
+        }
+
+        let light = light.unwrap_or(&self.light);
+        write_u64(&mut out, light.len() as u64);
+        for c in light.iter() {
+            write_color(&mut out, c);
+        }
+
+        // Written next to the old checkpoint first, so being killed while writing doesn't lose it
+        let temp = format!("{}.tmp", filename);
+        if let Err(e) = fs::write(&temp, out) {
+            return Err(format!("Error writing checkpoint: {}", e));
+        }
+        if let Err(e) = fs::rename(&temp, filename) {
+            return Err(format!("Error writing checkpoint: {}", e));
+        }
+        return Ok(());
+    }
+
+    /** Reads a state written by `save` into this one, which must have been created with the same
+     * dimensions and passes. Returns false if there is no such file. */
+    pub fn load(&mut self, filename: &str, signature: &str) -> Result<bool, String> {
+        let data = match fs::read(filename) {
+            Ok(d) => d,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Ok(false);
+            },
+            Err(e) => {
+                return Err(format!("Error reading checkpoint: {}", e));
+            }
+        };
+
+        let header = format!("{}\n{}\n", MAGIC, signature);
+        if !data.starts_with(header.as_bytes()) {
+            return Err("Checkpoint error: The checkpoint was rendered with different settings.".to_string());
+        }
+
+        let mut reader = Reader::new(&data[header.len()..]);
+        self.samples = reader.u64()?;
+        self.film.decode(&mut reader)?;
+
+        for p in self.pixels.iter_mut() {
+            p.samples = reader.u64()?;
+            let n = reader.u64()?;
+            let mean = reader.f32()?;
+            let m2 = reader.f32()?;
+            p.stats = PixelStats::from_state(n, mean, m2);
+            for c in p.aovs.iter_mut() {
+                *c = reader.color()?;
+            }
+            for layer in p.coverage.iter_mut() {
+                let count = reader.u64()?;
+                layer.clear();
+                for _ in 0..count {
+                    let id = reader.f32()?;
+                    let amount = reader.f32()?;
+                    layer.push((id, amount));
+                }
+            }
+        }
+
+        let count = reader.u64()?;
+        self.light.clear();
+        for _ in 0..count {
+            self.light.push(reader.color()?);
+        }
+        return Ok(true);
+    }
+}
+
+/** Reads the little endian values of a checkpoint one after the other */
+pub struct Reader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader {
+            data,
+            position: 0
+        }
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        if self.position + N > self.data.len() {
+            return Err("Checkpoint error: The checkpoint is truncated.".to_string());
+        }
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(&self.data[self.position..self.position + N]);
+        self.position += N;
+        return Ok(bytes);
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        return Ok(u64::from_le_bytes(self.bytes()?));
+    }
+
+    pub fn i64(&mut self) -> Result<i64, String> {
+        return Ok(i64::from_le_bytes(self.bytes()?));
+    }
+
+    pub fn f32(&mut self) -> Result<f32, String> {
+        return Ok(f32::from_le_bytes(self.bytes()?));
+    }
+
+    pub fn color(&mut self) -> Result<Color, String> {
+        let r = self.f32()?;
+        let g = self.f32()?;
+        let b = self.f32()?;
+        return Ok(Color::new(r, g, b));
+    }
+}
+
+pub fn write_u64(out: &mut Vec<u8>, v: u64) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+pub fn write_i64(out: &mut Vec<u8>, v: i64) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn write_f32(out: &mut Vec<u8>, v: f32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn write_color(out: &mut Vec<u8>, c: &Color) {
+    write_f32(out, c.r);
+    write_f32(out, c.g);
+    write_f32(out, c.b);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint_file(name: &str) -> String {
+        return std::env::temp_dir().join(format!("raybow_test_{}.checkpoint", name)).to_str().unwrap().to_string();
+    }
+
+    /* State of a 5x3 image with 2 passes and 1 cryptomatte layer, which some samples were added to */
+    fn sample_state() -> RenderState {
+        let mut state = RenderState::new(5, 3, Filter::Box, 2, 1);
+        state.samples = 3;
+        let mut tile = state.film.tile(0, 5, 0, 3);
+        tile.add_sample(1.5, 0.5, &Color::new(0.25, 0.5, 2.0), 1.0);
+        tile.add_sample(4.2, 2.7, &Color::new(0.1, 0.0, 0.3), 0.5);
+        state.film.merge(tile);
+
+        for (i, p) in state.pixels.iter_mut().enumerate() {
+            p.samples = i as u64;
+            p.stats.add(i as f32 * 0.1);
+            p.stats.add(0.3);
+            p.aovs[1] = Color::new(i as f32, 1.0, -2.0);
+            p.coverage[0] = vec![(0.5, i as f32); i % 3];
+        }
+        return state;
+    }
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let filename = checkpoint_file("round_trip");
+        let state = sample_state();
+        let light: Vec<Color> = (0..15).map(|i| Color::new(i as f32, 0.0, 1.0)).collect();
+        state.save(&filename, "settings", Some(&light)).unwrap();
+
+        let mut loaded = RenderState::new(5, 3, Filter::Box, 2, 1);
+        assert!(loaded.load(&filename, "settings").unwrap());
+        assert_eq!(loaded.samples, 3);
+
+        let (data, alpha) = state.film.resolve();
+        let (loaded_data, loaded_alpha) = loaded.film.resolve();
+        assert_eq!(alpha, loaded_alpha);
+        for (a, b) in data.iter().zip(loaded_data.iter()) {
+            assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+        }
+
+        for (a, b) in state.pixels.iter().zip(loaded.pixels.iter()) {
+            assert_eq!(a.samples, b.samples);
+            assert_eq!(a.stats.state(), b.stats.state());
+            for (c, d) in a.aovs.iter().zip(b.aovs.iter()) {
+                assert_eq!((c.r, c.g, c.b), (d.r, d.g, d.b));
+            }
+            assert_eq!(a.coverage, b.coverage);
+        }
+        assert_eq!(loaded.light.len(), light.len());
+        for (a, b) in light.iter().zip(loaded.light.iter()) {
+            assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+        }
+    }
+
+    #[test]
+    fn checkpoint_of_other_settings_is_rejected() {
+        let filename = checkpoint_file("other_settings");
+        sample_state().save(&filename, "seed 1", None).unwrap();
+
+        let mut loaded = RenderState::new(5, 3, Filter::Box, 2, 1);
+        assert!(loaded.load(&filename, "seed 2").is_err());
+        assert!(loaded.load(&filename, "seed 1").is_ok());
+    }
+
+    #[test]
+    fn missing_checkpoint_is_not_an_error() {
+        let mut state = RenderState::new(5, 3, Filter::Box, 2, 1);
+        assert!(!state.load(&checkpoint_file("missing"), "settings").unwrap());
+    }
+
+    #[test]
+    fn truncated_checkpoint_is_an_error() {
+        let filename = checkpoint_file("truncated");
+        sample_state().save(&filename, "settings", None).unwrap();
+        let data = fs::read(&filename).unwrap();
+        fs::write(&filename, &data[..data.len() - 5]).unwrap();
+
+        let mut loaded = RenderState::new(5, 3, Filter::Box, 2, 1);
+        assert!(loaded.load(&filename, "settings").is_err());
+    }
+}
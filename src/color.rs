@@ -37,16 +37,25 @@ impl Color {
         }
     }
 
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
     pub fn clamp(&mut self) {
         self.r = self.r.max(0.0).min(0.999);
         self.g = self.g.max(0.0).min(0.999);
         self.b = self.b.max(0.0).min(0.999);
     }
 
-    pub fn to_uint8_str(&self) -> String {
+    pub fn to_uint8(&self) -> [u8; 3] {
         let r = (self.r.sqrt() * 255.999) as u8;
         let g = (self.g.sqrt() * 255.999) as u8;
         let b = (self.b.sqrt() * 255.999) as u8;
+        [r, g, b]
+    }
+
+    pub fn to_uint8_str(&self) -> String {
+        let [r, g, b] = self.to_uint8();
         return format!("{} {} {}", r, g, b);
     }
 
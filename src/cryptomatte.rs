@@ -0,0 +1,146 @@
+use std::{collections::HashMap, fs, io::Error};
+
+use crate::structures::{scene::Scene, renderable::HitRecord};
+
+/** What objects are told apart by a cryptomatte layer */
+#[derive(Clone, Copy, PartialEq)]
+pub enum CryptomatteType {
+    Node,
+    Material
+}
+
+impl CryptomatteType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "node" => Some(CryptomatteType::Node),
+            "material" => Some(CryptomatteType::Material),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CryptomatteType::Node => "node",
+            CryptomatteType::Material => "material"
+        }
+    }
+}
+
+/** ID matte of one cryptomatte type. Names are hashed into IDs stored as floats,
+ * and every pixel keeps the IDs covering most of it along with their coverage. */
+pub struct CryptomatteLayer {
+    kind: CryptomatteType,
+    /* Node IDs or material indices to hashes */
+    hashes: HashMap<u64, u32>,
+    manifest: Vec<(String, u32)>
+}
+
+impl CryptomatteLayer {
+    pub fn new(kind: CryptomatteType, scene: &Scene) -> Self {
+        let names = match kind {
+            CryptomatteType::Node => scene.node_names(),
+            CryptomatteType::Material => {
+                scene.materials().names().iter().enumerate().map(|(i, name)| (i as u64, name.clone())).collect()
+            }
+        };
+
+        let mut hashes = HashMap::new();
+        let mut manifest = Vec::new();
+        for (key, name) in names {
+            let hash = CryptomatteLayer::hash(&name);
+            hashes.insert(key, hash);
+            manifest.push((name, hash));
+        }
+        manifest.sort();
+        manifest.dedup();
+
+        CryptomatteLayer {
+            kind, hashes, manifest
+        }
+    }
+
+    pub fn kind(&self) -> CryptomatteType {
+        self.kind
+    }
+
+    /* MurmurHash3 (x86, 32 bit) of the name, with the exponent bits adjusted so the
+     * hash read as a float is neither infinite, NaN nor denormal */
+    fn hash(name: &str) -> u32 {
+        let mut hash = murmur3_32(name.as_bytes(), 0);
+        let exponent = (hash >> 23) & 255;
+        if exponent == 0 || exponent == 255 {
+            hash ^= 1 << 23;
+        }
+        return hash;
+    }
+
+    /** ID of the object that was hit, as stored in the images */
+    pub fn id(&self, hit: &HitRecord) -> Option<f32> {
+        let key = match self.kind {
+            CryptomatteType::Node => hit.node(),
+            CryptomatteType::Material => hit.material().index() as u64
+        };
+        return self.hashes.get(&key).map(|h| f32::from_bits(*h));
+    }
+
+    /** Writes the names and their hashes as a JSON object */
+    pub fn write_manifest(&self, filename: &str) -> Result<(), Error> {
+        let entries: Vec<String> = self.manifest.iter().map(|(name, hash)| {
+            format!("\"{}\":\"{:08x}\"", name.replace('\\', "\\\\").replace('"', "\\\""), hash)
+        }).collect();
+        fs::write(format!("{}.json", filename), format!("{{{}}}\n", entries.join(",")))
+    }
+}
+
+/** Adds coverage of an ID to the (ID, coverage) pairs of a pixel */
+pub fn add_coverage(coverage: &mut Vec<(f32, f32)>, id: f32, weight: f32) {
+    match coverage.iter_mut().find(|(i, _)| i.to_bits() == id.to_bits()) {
+        Some(c) => {
+            c.1 += weight;
+        },
+        None => {
+            coverage.push((id, weight));
+        }
+    }
+}
+
+/** The `ranks` IDs with the highest coverage, padded with zeros */
+pub fn top_coverage(mut coverage: Vec<(f32, f32)>, ranks: usize) -> Vec<(f32, f32)> {
+    coverage.sort_by(|a, b| b.1.total_cmp(&a.1));
+    coverage.resize(ranks, (0.0, 0.0));
+    return coverage;
+}
+
+fn murmur3_32(key: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+
+    let mix = |k: u32| -> u32 {
+        k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2)
+    };
+
+    let mut h = seed;
+    let chunks = key.chunks_exact(4);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        h ^= mix(k);
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+
+    if !tail.is_empty() {
+        let mut k = 0u32;
+        for (i, b) in tail.iter().enumerate() {
+            k |= (*b as u32) << (8 * i);
+        }
+        h ^= mix(k);
+    }
+
+    h ^= key.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85ebca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2ae35);
+    h ^= h >> 16;
+    return h;
+}
@@ -0,0 +1,175 @@
+use crossbeam::thread;
+
+use crate::color::Color;
+
+/** Per-pixel features of the first visible surface guiding the denoiser, as gathered by
+ * the albedo, normal, depth and variance render passes */
+pub struct Features<'a> {
+    pub albedo: &'a [Color],
+    pub normal: &'a [Color],
+    pub depth: &'a [Color],
+    pub variance: &'a [Color]
+}
+
+/* Number of filter passes, the footprint doubles with every one of them */
+const ITERATIONS: u32 = 5;
+/* B3 spline kernel of the a-trous wavelet transform */
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/* How many standard deviations of noise a luminance difference may span and still be blurred */
+const SIGMA_LUMINANCE: f32 = 4.0;
+/* Exponent of the cosine between normals */
+const SIGMA_NORMAL: f32 = 64.0;
+/* Relative depth difference allowed per pixel of distance */
+const SIGMA_DEPTH: f32 = 0.05;
+const SIGMA_ALBEDO: f32 = 0.1;
+
+/** Removes noise from a rendered image with an edge-avoiding a-trous wavelet filter
+ * (Dammertz et al.), whose edge-stopping weights come from the feature buffers.
+ * Neighbours are averaged unless the surface changes, i.e. the normal, depth or albedo differs,
+ * or their brightness differs by more than the estimated noise explains (as in SVGF). */
+pub fn denoise(image: &[Color], features: &Features, width: u64, height: u64, threads: u64) -> Vec<Color> {
+    let width = width as usize;
+    let height = height as usize;
+
+    let mut color: Vec<Color> = image.iter().map(|c| c.copy()).collect();
+    let mut variance = blur_variance(features.variance, width, height);
+
+    for i in 0..ITERATIONS {
+        let step = 1 << i;
+        let pass = Pass { color: &color, variance: &variance, features, width, height, step };
+        let (c, v) = filter_pass(&pass, threads.max(1) as usize);
+        color = c;
+        variance = v;
+    }
+
+    return color;
+}
+
+/* Variance estimated from a few samples is noisy itself, a small blur steadies it */
+fn blur_variance(variance: &[Color], width: usize, height: usize) -> Vec<f32> {
+    let mut blurred = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = 0.0;
+            let mut weight = 0.0;
+            for dy in -1..=1_i64 {
+                for dx in -1..=1_i64 {
+                    let qx = x as i64 + dx;
+                    let qy = y as i64 + dy;
+                    if qx < 0 || qy < 0 || qx >= width as i64 || qy >= height as i64 {
+                        continue;
+                    }
+                    let w = KERNEL[(dx + 2) as usize] * KERNEL[(dy + 2) as usize];
+                    sum += w * variance[qy as usize * width + qx as usize].r;
+                    weight += w;
+                }
+            }
+            blurred.push(sum / weight);
+        }
+    }
+    return blurred;
+}
+
+/* Input of an a-trous pass with taps `step` pixels apart */
+struct Pass<'a> {
+    color: &'a [Color],
+    variance: &'a [f32],
+    features: &'a Features<'a>,
+    width: usize,
+    height: usize,
+    step: i64
+}
+
+/* One a-trous pass, rows are split among the threads */
+fn filter_pass(pass: &Pass, threads: usize) -> (Vec<Color>, Vec<f32>) {
+    let (width, height) = (pass.width, pass.height);
+    let rows_per_thread = height.div_ceil(threads);
+
+    let parts = thread::scope(|s| {
+        let handles: Vec<_> = (0..threads).map(|t| {
+            s.spawn(move |_| {
+                let start = (t * rows_per_thread).min(height);
+                let end = ((t + 1) * rows_per_thread).min(height);
+                let mut c_part = Vec::with_capacity((end - start) * width);
+                let mut v_part = Vec::with_capacity((end - start) * width);
+                for y in start..end {
+                    for x in 0..width {
+                        let (c, v) = filter_pixel(pass, x, y);
+                        c_part.push(c);
+                        v_part.push(v);
+                    }
+                }
+                (c_part, v_part)
+            })
+        }).collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect::<Vec<_>>()
+    }).unwrap();
+
+    let mut c_out = Vec::with_capacity(width * height);
+    let mut v_out = Vec::with_capacity(width * height);
+    for (c, v) in parts {
+        c_out.extend(c);
+        v_out.extend(v);
+    }
+    return (c_out, v_out);
+}
+
+fn filter_pixel(pass: &Pass, x: usize, y: usize) -> (Color, f32) {
+    let (color, variance, features) = (pass.color, pass.variance, pass.features);
+    let (width, height, step) = (pass.width, pass.height, pass.step);
+    let p = y * width + x;
+    let luminance = color[p].luminance();
+    let sigma_l = SIGMA_LUMINANCE * variance[p].sqrt() + 1e-4;
+    let n_p = &features.normal[p];
+    let d_p = features.depth[p].r;
+    let a_p = &features.albedo[p];
+    let background = n_p.r == 0.0 && n_p.g == 0.0 && n_p.b == 0.0;
+
+    let mut sum = Color::new(0.0, 0.0, 0.0);
+    let mut sum_variance = 0.0;
+    let mut sum_weight = 0.0;
+
+    for (ky, dy) in (-2..=2_i64).enumerate() {
+        for (kx, dx) in (-2..=2_i64).enumerate() {
+            let qx = x as i64 + dx * step;
+            let qy = y as i64 + dy * step;
+            if qx < 0 || qy < 0 || qx >= width as i64 || qy >= height as i64 {
+                continue;
+            }
+            let q = qy as usize * width + qx as usize;
+
+            let n_q = &features.normal[q];
+            let w_normal = if background {
+                if n_q.r == 0.0 && n_q.g == 0.0 && n_q.b == 0.0 { 1.0 } else { 0.0 }
+            } else {
+                // Normals averaged over a pixel are shorter than one at edges
+                let len = ((n_p.r * n_p.r + n_p.g * n_p.g + n_p.b * n_p.b) * (n_q.r * n_q.r + n_q.g * n_q.g + n_q.b * n_q.b)).sqrt();
+                let cos = if len > 0.0 { (n_p.r * n_q.r + n_p.g * n_q.g + n_p.b * n_q.b) / len } else { 0.0 };
+                cos.max(0.0).powf(SIGMA_NORMAL)
+            };
+            if w_normal <= 0.0 {
+                continue;
+            }
+
+            let d_q = features.depth[q].r;
+            let w_depth = (-(d_p - d_q).abs() / (SIGMA_DEPTH * d_p.max(1e-3) * step as f32)).exp();
+
+            let a_q = &features.albedo[q];
+            let da = (a_p.r - a_q.r).powi(2) + (a_p.g - a_q.g).powi(2) + (a_p.b - a_q.b).powi(2);
+            let w_albedo = (-da / (SIGMA_ALBEDO * SIGMA_ALBEDO)).exp();
+
+            let w_luminance = (-(luminance - color[q].luminance()).abs() / sigma_l).exp();
+
+            let w = KERNEL[kx] * KERNEL[ky] * w_normal * w_depth * w_albedo * w_luminance;
+            sum = Color::add(&sum, &Color::scale(&color[q], w));
+            sum_variance += w * w * variance[q];
+            sum_weight += w;
+        }
+    }
+
+    if sum_weight <= 0.0 {
+        return (color[p].copy(), variance[p]);
+    }
+    return (Color::scale(&sum, 1.0 / sum_weight), sum_variance / (sum_weight * sum_weight));
+}
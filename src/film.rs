@@ -0,0 +1,165 @@
+use crate::{color::Color, filter::Filter, checkpoint::{Reader, write_i64}};
+
+/* Scale of the fixed point sums in the pixels. Integer sums don't depend on the order in which
+ * samples are added, so the image is the same however the threads split it. */
+const FIXED_POINT_SCALE: f64 = (1u64 << 32) as f64;
+
+/* Weighted sums of the samples around a pixel */
+#[derive(Clone, Copy)]
+struct FilmPixel {
+    color: [i64; 3],
+    alpha: i64,
+    weight: i64
+}
+
+impl FilmPixel {
+    fn new() -> Self {
+        FilmPixel {
+            color: [0; 3],
+            alpha: 0,
+            weight: 0
+        }
+    }
+
+    fn add(&mut self, other: &FilmPixel) {
+        for i in 0..3 {
+            self.color[i] = self.color[i].saturating_add(other.color[i]);
+        }
+        self.alpha = self.alpha.saturating_add(other.alpha);
+        self.weight = self.weight.saturating_add(other.weight);
+    }
+}
+
+fn to_fixed(v: f32) -> i64 {
+    return (v as f64 * FIXED_POINT_SCALE) as i64;
+}
+
+/** Image the camera samples are accumulated in. Every sample is splatted onto all the pixels
+ * whose reconstruction filter reaches it, and each pixel is the weighted average of its samples. */
+pub struct Film {
+    width: u64,
+    height: u64,
+    filter: Filter,
+    pixels: Vec<FilmPixel>
+}
+
+impl Film {
+    pub fn new(width: u64, height: u64, filter: Filter) -> Self {
+        Film {
+            width,
+            height,
+            filter,
+            pixels: vec![FilmPixel::new(); (width * height) as usize]
+        }
+    }
+
+    /** Tile for the samples of the given pixels, covering the neighbours their filter reaches */
+    pub fn tile(&self, x_start: u64, x_end: u64, y_start: u64, y_end: u64) -> FilmTile {
+        let margin = self.filter.radius().ceil() as u64;
+        let x_start = x_start.saturating_sub(margin);
+        let y_start = y_start.saturating_sub(margin);
+        let x_end = (x_end + margin).min(self.width);
+        let y_end = (y_end + margin).min(self.height);
+
+        FilmTile {
+            filter: self.filter,
+            x_start,
+            x_end,
+            y_start,
+            y_end,
+            pixels: vec![FilmPixel::new(); ((x_end - x_start) * (y_end - y_start)) as usize]
+        }
+    }
+
+    /** Adds the samples of a finished tile */
+    pub fn merge(&mut self, tile: FilmTile) {
+        let tile_width = tile.x_end - tile.x_start;
+        for y in tile.y_start..tile.y_end {
+            for x in tile.x_start..tile.x_end {
+                let p = &tile.pixels[((y - tile.y_start) * tile_width + x - tile.x_start) as usize];
+                self.pixels[(y * self.width + x) as usize].add(p);
+            }
+        }
+    }
+
+    /** Appends the sums of the pixels to a checkpoint */
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        for p in self.pixels.iter() {
+            for v in [p.color[0], p.color[1], p.color[2], p.alpha, p.weight] {
+                write_i64(out, v);
+            }
+        }
+    }
+
+    /** Reads the sums written by `encode` */
+    pub fn decode(&mut self, reader: &mut Reader) -> Result<(), String> {
+        for p in self.pixels.iter_mut() {
+            p.color = [reader.i64()?, reader.i64()?, reader.i64()?];
+            p.alpha = reader.i64()?;
+            p.weight = reader.i64()?;
+        }
+        return Ok(());
+    }
+
+    /** Colors and alpha of the pixels */
+    pub fn resolve(&self) -> (Vec<Color>, Vec<f32>) {
+        let mut colors = Vec::with_capacity(self.pixels.len());
+        let mut alpha = Vec::with_capacity(self.pixels.len());
+        for p in self.pixels.iter() {
+            if p.weight <= 0 {
+                colors.push(Color::new(0.0, 0.0, 0.0));
+                alpha.push(0.0);
+                continue;
+            }
+            let inv_weight = 1.0 / p.weight as f64;
+            colors.push(Color::new(
+                (p.color[0] as f64 * inv_weight) as f32,
+                (p.color[1] as f64 * inv_weight) as f32,
+                (p.color[2] as f64 * inv_weight) as f32));
+            alpha.push((p.alpha as f64 * inv_weight) as f32);
+        }
+        return (colors, alpha);
+    }
+}
+
+/** Part of a film a render thread splats its samples onto, merged into the film when done */
+pub struct FilmTile {
+    filter: Filter,
+    x_start: u64,
+    x_end: u64,
+    y_start: u64,
+    y_end: u64,
+    pixels: Vec<FilmPixel>
+}
+
+impl FilmTile {
+    /** Adds a sample taken at the given raster position, where pixel (x, y) spans from x to x + 1 */
+    pub fn add_sample(&mut self, x: f32, y: f32, c: &Color, alpha: f32) {
+        if !(c.r.is_finite() && c.g.is_finite() && c.b.is_finite() && alpha.is_finite()) {
+            return;
+        }
+
+        // Pixels whose centers lie within the radius
+        let radius = self.filter.radius();
+        let x0 = ((x - 0.5 - radius).ceil() as i64).max(self.x_start as i64);
+        let x1 = ((x - 0.5 + radius).floor() as i64).min(self.x_end as i64 - 1);
+        let y0 = ((y - 0.5 - radius).ceil() as i64).max(self.y_start as i64);
+        let y1 = ((y - 0.5 + radius).floor() as i64).min(self.y_end as i64 - 1);
+
+        let tile_width = (self.x_end - self.x_start) as i64;
+        for py in y0..=y1 {
+            for px in x0..=x1 {
+                let weight = self.filter.evaluate(px as f32 + 0.5 - x, py as f32 + 0.5 - y);
+                if weight == 0.0 {
+                    continue;
+                }
+                let splat = FilmPixel {
+                    color: [to_fixed(c.r * weight), to_fixed(c.g * weight), to_fixed(c.b * weight)],
+                    alpha: to_fixed(alpha * weight),
+                    weight: to_fixed(weight)
+                };
+                self.pixels[((py - self.y_start as i64) * tile_width + px - self.x_start as i64) as usize].add(&splat);
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::f32::consts::PI;
+
+/** Pixel reconstruction filter, weighting the samples around a pixel by their distance
+ * to its center. All of them are separable, the weight is the product of the filter along x and y. */
+#[derive(Clone, Copy, PartialEq)]
+pub enum Filter {
+    /** Averages the samples inside the pixel */
+    Box,
+    /** Weights fall off linearly up to the centers of the neighbouring pixels */
+    Tent,
+    /** Gaussian with a standard deviation of half a pixel, soft without ringing */
+    Gaussian,
+    /** Mitchell-Netravali cubic with B = C = 1/3, sharp with little ringing */
+    Mitchell,
+    /** Sinc windowed by a wider sinc (Lanczos 2), the sharpest, rings around high contrast edges */
+    Lanczos
+}
+
+impl Filter {
+    const GAUSSIAN_SIGMA: f32 = 0.5;
+    const MITCHELL_B: f32 = 1.0 / 3.0;
+    const MITCHELL_C: f32 = 1.0 / 3.0;
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "box" => Some(Filter::Box),
+            "tent" => Some(Filter::Tent),
+            "gaussian" => Some(Filter::Gaussian),
+            "mitchell" => Some(Filter::Mitchell),
+            "lanczos" => Some(Filter::Lanczos),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Box => "box",
+            Filter::Tent => "tent",
+            Filter::Gaussian => "gaussian",
+            Filter::Mitchell => "mitchell",
+            Filter::Lanczos => "lanczos"
+        }
+    }
+
+    /** Distance from the pixel center, in pixels, past which samples have no weight */
+    pub fn radius(&self) -> f32 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+            Filter::Lanczos => 2.0
+        }
+    }
+
+    /** Weight of a sample at the given offset from the pixel center, may be negative */
+    pub fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        return self.evaluate_1d(dx) * self.evaluate_1d(dy);
+    }
+
+    fn evaluate_1d(&self, d: f32) -> f32 {
+        let d = d.abs();
+        let radius = self.radius();
+        if d > radius {
+            return 0.0;
+        }
+
+        return match self {
+            Filter::Box => 1.0,
+            Filter::Tent => radius - d,
+            Filter::Gaussian => {
+                // Shifted down so the weight reaches zero at the radius
+                let s2 = 2.0 * Filter::GAUSSIAN_SIGMA * Filter::GAUSSIAN_SIGMA;
+                ((-d * d / s2).exp() - (-radius * radius / s2).exp()).max(0.0)
+            },
+            Filter::Mitchell => {
+                let b = Filter::MITCHELL_B;
+                let c = Filter::MITCHELL_C;
+                let x = 2.0 * d / radius;
+                let w = if x > 1.0 {
+                    (-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x
+                        + (-12.0 * b - 48.0 * c) * x + (8.0 * b + 24.0 * c)
+                } else {
+                    (12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                        + (6.0 - 2.0 * b)
+                };
+                w / 6.0
+            },
+            Filter::Lanczos => Filter::sinc(d) * Filter::sinc(d / radius)
+        };
+    }
+
+    fn sinc(x: f32) -> f32 {
+        if x < 1e-5 {
+            return 1.0;
+        }
+        return (PI * x).sin() / (PI * x);
+    }
+}
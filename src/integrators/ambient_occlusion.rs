@@ -0,0 +1,48 @@
+use crate::{color::Color, math::vector3::Vector3, ray::Ray, structures::scene::Scene};
+
+use super::integrator::{Integrator, pass_through};
+
+/** Shades surfaces by how much of the hemisphere above them is open within `distance` */
+pub struct AmbientOcclusion {
+    distance: f32
+}
+
+impl AmbientOcclusion {
+    pub fn new(distance: f32) -> Self {
+        AmbientOcclusion {
+            distance
+        }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn camera_ray_color(&self, scene: &Scene, ray: Ray, f: f32) -> (Color, f32) {
+        let hit = match scene.trace(&ray, 0.0001, 10000.0, f) {
+            Some(h) => h,
+            None => {
+                return (Color::new(1.0, 1.0, 1.0), 1.0);
+            }
+        };
+
+        if scene.material(hit.material()).alpha(&hit) < crate::random::random() {
+            return self.camera_ray_color(scene, pass_through(&ray, &hit), f);
+        }
+
+        // Cosine weighted direction around the normal
+        let n = hit.n();
+        let mut dir = n + &Vector3::random_in_unit_sphere();
+        if dir.near_zero() {
+            dir = n.copy();
+        }
+        let probe = Ray::new(hit.p() + &(n * 0.0001), dir.normalize());
+
+        return match scene.trace(&probe, 0.0001, self.distance, f) {
+            Some(_) => (Color::new(0.0, 0.0, 0.0), 1.0),
+            None => (Color::new(1.0, 1.0, 1.0), 1.0)
+        };
+    }
+
+    fn supports_spectral(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,555 @@
+use std::{f32::consts::PI, sync::Mutex};
+
+use crate::{color::Color, math::vector3::Vector3, ray::Ray, spectrum, structures::{scene::Scene, renderable::HitRecord, camera::Camera}};
+
+use super::integrator::{Integrator, to_ray_spectrum, light_attenuation, pass_through, background};
+
+#[derive(Clone, Copy, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface
+}
+
+/* Vertex of a camera or light subpath. Pdfs are per unit area: pdf_fwd of generating
+ * the vertex from the previous one on its own subpath, pdf_rev of generating it
+ * from the next one, as if the path had been sampled from the other end. */
+struct Vertex {
+    kind: VertexKind,
+    p: Vector3,
+    n: Vector3,
+    hit: Option<HitRecord>,
+    wo: Vector3,
+    beta: Color,
+    delta: bool,
+    pdf_fwd: f32,
+    pdf_rev: f32
+}
+
+impl Vertex {
+    fn new(kind: VertexKind, p: Vector3, n: Vector3, hit: Option<HitRecord>, wo: Vector3, beta: Color, pdf_fwd: f32) -> Self {
+        Vertex {
+            kind, p, n, hit, wo, beta, pdf_fwd,
+            delta: false,
+            pdf_rev: 0.0
+        }
+    }
+
+    /* Vertices that can be joined to another subpath with a shadow ray */
+    fn connectible(&self) -> bool {
+        self.kind != VertexKind::Surface || !self.delta
+    }
+}
+
+/* Pdf values of a vertex as seen by the MIS weight computation */
+#[derive(Clone, Copy)]
+struct VertexPdf {
+    fwd: f32,
+    rev: f32,
+    delta: bool
+}
+
+/* What the subpaths of a sample are traced in: the scene, seen through its camera, at a frame */
+struct Context<'a> {
+    scene: &'a Scene,
+    camera: &'a Camera,
+    f: f32
+}
+
+/* Camera and light subpaths of a sample, to be connected */
+struct Subpaths<'a> {
+    camera: &'a [Vertex],
+    light: &'a [Vertex]
+}
+
+/** Bidirectional path tracer after Veach, "Robust Monte Carlo Methods for Light Transport Simulation".
+ * Every sample traces a camera subpath and a light subpath starting on an emissive object, and
+ * combines all ways of connecting their vertices with the power heuristic.
+ *
+ * Mirrors and glass take part in the subpaths but can't be connected to, and the environment
+ * is only found by camera subpaths leaving the scene. Participating media are ignored, and
+ * shadow catchers are rendered like the diffuse surfaces they are to other objects.
+ * Connections of light subpaths straight to the camera land in other pixels; they are kept
+ * in a separate light image that is added once the frame is rendered. They need a camera
+ * without aperture, otherwise that strategy is left out. The light image is summed in fixed
+ * point, so the order in which the threads splat onto it doesn't change the result. */
+pub struct BidirectionalPathTracer {
+    max_depth: u64,
+    transparent: bool,
+    width: u64,
+    height: u64,
+    light_image: Mutex<Vec<[i64; 3]>>
+}
+
+impl BidirectionalPathTracer {
+    /* Bounces after which subpaths become candidates for Russian roulette */
+    const ROULETTE_DEPTH: usize = 3;
+    /* Fixed point scale of the light image */
+    const SPLAT_SCALE: f64 = (1u64 << 32) as f64;
+
+    pub fn new(max_depth: u64, transparent: bool, width: u64, height: u64) -> Self {
+        let mut light_image = Vec::new();
+        for _ in 0..width*height {
+            light_image.push([0; 3]);
+        }
+
+        BidirectionalPathTracer {
+            max_depth,
+            transparent,
+            width,
+            height,
+            light_image: Mutex::new(light_image)
+        }
+    }
+
+    /* Converts a solid angle pdf at vertex `from` into a pdf per unit area at vertex `to` */
+    fn convert_density(pdf: f32, from: &Vertex, to: &Vertex) -> f32 {
+        let d = &to.p - &from.p;
+        let dist2 = &d * &d;
+        if dist2 <= 0.0 {
+            return 0.0;
+        }
+        if to.kind == VertexKind::Camera {
+            return pdf / dist2;
+        }
+        let cos = (&(&d * (1.0 / dist2.sqrt())) * &to.n).abs();
+        return pdf * cos / dist2;
+    }
+
+    /* Pdf per unit area of vertex `cur` sampling `next`, having been reached from `prev` */
+    fn pdf(scene: &Scene, camera: &Camera, prev: Option<&Vertex>, cur: &Vertex, next: &Vertex) -> f32 {
+        let to_next = (&next.p - &cur.p).normalize();
+        let pdf_dir = match cur.kind {
+            VertexKind::Camera => camera.pdf_direction(&to_next),
+            VertexKind::Light => (&to_next * &cur.n).max(0.0) / PI,
+            VertexKind::Surface => {
+                let hit = cur.hit.as_ref().unwrap();
+                let wo = match prev {
+                    Some(v) => (&v.p - &cur.p).normalize(),
+                    None => cur.wo.copy()
+                };
+                match scene.material(hit.material()).eval(&wo, &to_next, hit) {
+                    Some((_, pdf)) => pdf,
+                    None => 0.0
+                }
+            }
+        };
+        return BidirectionalPathTracer::convert_density(pdf_dir, cur, next);
+    }
+
+    /* Extends a subpath by following sampled scattering directions. Returns the light of the
+     * environment if a camera subpath leaves the scene. */
+    fn random_walk(&self, context: &Context, ray: Ray, beta: Color, pdf_dir: f32, path: &mut Vec<Vertex>,
+            max_vertices: usize) -> Color {
+        let scene = context.scene;
+        let camera = path[0].kind == VertexKind::Camera;
+        let mut ray = ray;
+        let mut beta = beta;
+        let mut pdf_dir = pdf_dir;
+
+        while path.len() < max_vertices {
+            let hit = match scene.trace(&ray, 0.0001, 10000.0, context.f) {
+                Some(h) => h,
+                None => {
+                    if camera {
+                        return &beta * &background(scene, &ray);
+                    }
+                    break;
+                }
+            };
+
+            let material = scene.material(hit.material());
+            if material.alpha(&hit) < crate::random::random() {
+                ray = pass_through(&ray, &hit);
+                continue;
+            }
+
+            let wo = -ray.get_direction().normalize();
+            let mut vertex = Vertex::new(VertexKind::Surface, hit.p().copy(), hit.n().copy(), None, wo.copy(), beta.copy(), 0.0);
+            vertex.pdf_fwd = BidirectionalPathTracer::convert_density(pdf_dir, path.last().unwrap(), &vertex);
+
+            let scatter = material.scatter(&ray, &hit);
+            vertex.hit = Some(hit);
+            path.push(vertex);
+
+            let mut scatter = match scatter {
+                Some(s) => s,
+                None => break
+            };
+            if path.len() >= max_vertices {
+                break;
+            }
+
+            let n = path.len();
+            let wi = scatter.ray.get_direction().normalize();
+            let hit = path[n - 1].hit.as_ref().unwrap();
+            let (pdf_fwd, pdf_rev) = match material.eval(&wo, &wi, hit) {
+                Some((_, pdf)) => {
+                    let rev = material.eval(&wi, &wo, hit).map(|(_, p)| p).unwrap_or(0.0);
+                    (pdf, rev)
+                },
+                None => {
+                    path[n - 1].delta = true;
+                    (0.0, 0.0)
+                }
+            };
+
+            beta = if camera {
+                &beta * &to_ray_spectrum(&ray, scatter.attenuation)
+            } else {
+                &beta * &light_attenuation(&ray, &scatter)
+            };
+            if n > BidirectionalPathTracer::ROULETTE_DEPTH {
+                let q = beta.r.max(beta.g).max(beta.b).min(0.95);
+                if q <= 0.0 || crate::random::random() >= q {
+                    break;
+                }
+                beta = Color::scale(&beta, 1.0 / q);
+            }
+
+            let rev = BidirectionalPathTracer::convert_density(pdf_rev, &path[n - 1], &path[n - 2]);
+            path[n - 2].pdf_rev = rev;
+
+            pdf_dir = pdf_fwd;
+            scatter.ray.set_wavelength(ray.get_wavelength());
+            ray = scatter.ray;
+        }
+
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    /* Starts a light subpath on a random point of an emissive object */
+    fn light_subpath(&self, context: &Context, wavelength: Option<f32>, max_vertices: usize) -> Vec<Vertex> {
+        let scene = context.scene;
+        let mut path = Vec::new();
+        let (light, pdf_area) = match scene.sample_light(context.f) {
+            Some(l) => l,
+            None => {
+                return path;
+            }
+        };
+
+        let n = light.n().copy();
+        let mut probe = Ray::new(light.p().copy(), n.copy());
+        probe.set_wavelength(wavelength);
+        let le = to_ray_spectrum(&probe, scene.material(light.material()).emitted(&light));
+
+        // Cosine weighted emission, so the cosine and the pdf cancel out except for PI
+        let mut dir = &n + &Vector3::random_in_unit_sphere();
+        if dir.near_zero() {
+            dir = n.copy();
+        }
+        let dir = dir.normalize();
+        let pdf_dir = (&dir * &n).max(1e-6) / PI;
+
+        let beta = Color::scale(&le, PI / pdf_area);
+        let vertex = Vertex::new(VertexKind::Light, light.p().copy(), n, Some(light), Vector3::new(0.0, 0.0, 0.0),
+            Color::scale(&le, 1.0 / pdf_area), pdf_area);
+        path.push(vertex);
+
+        let mut ray = Ray::new(path[0].p.copy(), dir);
+        ray.set_wavelength(wavelength);
+        self.random_walk(context, ray, beta, pdf_dir, &mut path, max_vertices);
+        return path;
+    }
+
+    /* Whether nothing blocks the segment between two points */
+    fn visible(scene: &Scene, a: &Vector3, b: &Vector3, f: f32) -> bool {
+        let d = b - a;
+        let dist = d.length();
+        let mut ray = Ray::new(a.copy(), &d * (1.0 / dist));
+        let mut travelled = 0.0;
+
+        loop {
+            let hit = match scene.trace(&ray, 0.0001, dist - travelled, f) {
+                Some(h) => h,
+                None => {
+                    return true;
+                }
+            };
+            if hit.t() >= (dist - travelled) * 0.999 {
+                return true;
+            }
+            if scene.material(hit.material()).alpha(&hit) >= crate::random::random() {
+                return false;
+            }
+            travelled += hit.t();
+            ray = pass_through(&ray, &hit);
+        }
+    }
+
+    /* Contribution of the strategy using s light and t camera vertices, unweighted. For t = 1
+     * also returns the pixel the light subpath was connected to. */
+    fn connect(&self, context: &Context, paths: &Subpaths, s: usize, t: usize, sampled: &mut Option<Vertex>,
+            ray: &Ray) -> (Color, Option<(u64, u64)>) {
+        let Context { scene, camera, f } = *context;
+        let Subpaths { camera: camera_path, light: light_path } = *paths;
+        let black = Color::new(0.0, 0.0, 0.0);
+
+        if s == 0 {
+            let pt = &camera_path[t - 1];
+            if pt.kind != VertexKind::Surface {
+                return (black, None);
+            }
+            let hit = pt.hit.as_ref().unwrap();
+            let material = scene.material(hit.material());
+            if !material.is_emissive() {
+                return (black, None);
+            }
+            return (&pt.beta * &to_ray_spectrum(ray, material.emitted(hit)), None);
+        }
+
+        if t == 1 {
+            let qs = &light_path[s - 1];
+            if !qs.connectible() || camera.aperture_size() > 0.0 {
+                return (black, None);
+            }
+            let pixel = match camera.project(&qs.p, self.width, self.height) {
+                Some(px) => px,
+                None => {
+                    return (black, None);
+                }
+            };
+
+            let to_camera = camera.location() - &qs.p;
+            let dist2 = &to_camera * &to_camera;
+            let w = &to_camera * (1.0 / dist2.sqrt());
+            let f_cos = match qs.kind {
+                VertexKind::Light => {
+                    let cos = (&w * &qs.n).max(0.0);
+                    Color::new(cos, cos, cos)
+                },
+                _ => {
+                    let hit = qs.hit.as_ref().unwrap();
+                    match scene.material(hit.material()).eval(&qs.wo, &w, hit) {
+                        Some((fc, _)) => to_ray_spectrum(ray, fc),
+                        None => {
+                            return (black, None);
+                        }
+                    }
+                }
+            };
+
+            let importance = camera.pdf_direction(&-&w) / dist2;
+            if importance <= 0.0 || f_cos.luminance() <= 0.0 || !BidirectionalPathTracer::visible(scene, &qs.p, camera.location(), f) {
+                return (black, None);
+            }
+            return (Color::scale(&(&qs.beta * &f_cos), importance), Some(pixel));
+        }
+
+        let pt = &camera_path[t - 1];
+        if !pt.connectible() {
+            return (black, None);
+        }
+        let hit_t = pt.hit.as_ref().unwrap();
+        let material_t = scene.material(hit_t.material());
+
+        if s == 1 {
+            // A fresh point on a light, like next event estimation
+            let (light, pdf_area) = match scene.sample_light(f) {
+                Some(l) => l,
+                None => {
+                    return (black, None);
+                }
+            };
+            let d = light.p() - &pt.p;
+            let dist2 = &d * &d;
+            let w = &d * (1.0 / dist2.sqrt());
+            let cos_light = (&-&w * light.n()).max(0.0);
+            let le = to_ray_spectrum(ray, scene.material(light.material()).emitted(&light));
+            let f_cos = match material_t.eval(&pt.wo, &w, hit_t) {
+                Some((fc, _)) => to_ray_spectrum(ray, fc),
+                None => {
+                    return (black, None);
+                }
+            };
+            if cos_light <= 0.0 || f_cos.luminance() <= 0.0 || le.luminance() <= 0.0
+                || !BidirectionalPathTracer::visible(scene, &pt.p, light.p(), f) {
+                return (black, None);
+            }
+
+            let vertex = Vertex::new(VertexKind::Light, light.p().copy(), light.n().copy(), Some(light),
+                Vector3::new(0.0, 0.0, 0.0), Color::scale(&le, 1.0 / pdf_area), pdf_area);
+            let c = Color::scale(&(&(&pt.beta * &f_cos) * &le), cos_light / (dist2 * pdf_area));
+            *sampled = Some(vertex);
+            return (c, None);
+        }
+
+        let qs = &light_path[s - 1];
+        if !qs.connectible() {
+            return (black, None);
+        }
+        let hit_s = qs.hit.as_ref().unwrap();
+        let d = &qs.p - &pt.p;
+        let dist2 = &d * &d;
+        let w = &d * (1.0 / dist2.sqrt());
+
+        let f_t = material_t.eval(&pt.wo, &w, hit_t);
+        let f_s = scene.material(hit_s.material()).eval(&qs.wo, &-&w, hit_s);
+        let (f_t, f_s) = match (f_t, f_s) {
+            (Some((a, _)), Some((b, _))) => (to_ray_spectrum(ray, a), to_ray_spectrum(ray, b)),
+            _ => {
+                return (black, None);
+            }
+        };
+        if f_t.luminance() <= 0.0 || f_s.luminance() <= 0.0 || !BidirectionalPathTracer::visible(scene, &pt.p, &qs.p, f) {
+            return (black, None);
+        }
+
+        let c = &(&pt.beta * &f_t) * &(&f_s * &qs.beta);
+        return (Color::scale(&c, 1.0 / dist2), None);
+    }
+
+    /* Power heuristic weight of the strategy (s, t) against all others that could have produced the same path */
+    fn mis_weight(&self, context: &Context, paths: &Subpaths, sampled: Option<&Vertex>, s: usize, t: usize) -> f32 {
+        if s + t == 2 {
+            return 1.0;
+        }
+        let Context { scene, camera, f } = *context;
+        let Subpaths { camera: camera_path, light: light_path } = *paths;
+
+        let qs = if s == 1 { sampled.or(light_path.first()) } else if s > 1 { Some(&light_path[s - 1]) } else { None };
+        let pt = &camera_path[t - 1];
+
+        let mut cam: Vec<VertexPdf> = camera_path[..t].iter().map(|v| VertexPdf { fwd: v.pdf_fwd, rev: v.pdf_rev, delta: v.delta }).collect();
+        let mut light: Vec<VertexPdf> = light_path[..s].iter().map(|v| VertexPdf { fwd: v.pdf_fwd, rev: v.pdf_rev, delta: v.delta }).collect();
+        if s == 1 {
+            if let Some(v) = qs {
+                light[0] = VertexPdf { fwd: v.pdf_fwd, rev: v.pdf_rev, delta: false };
+            }
+        }
+
+        let pt_minus = if t > 1 { Some(&camera_path[t - 2]) } else { None };
+        let qs_minus = if s > 1 { Some(&light_path[s - 2]) } else { None };
+
+        // Densities at the connection, as if the vertices had been sampled from the other side
+        cam[t - 1].delta = false;
+        match qs {
+            Some(q) => {
+                light[s - 1].delta = false;
+                light[s - 1].rev = BidirectionalPathTracer::pdf(scene, camera, pt_minus, pt, q);
+                cam[t - 1].rev = BidirectionalPathTracer::pdf(scene, camera, qs_minus, q, pt);
+                if let Some(pm) = pt_minus {
+                    cam[t - 2].rev = BidirectionalPathTracer::pdf(scene, camera, Some(q), pt, pm);
+                }
+                if let Some(qm) = qs_minus {
+                    light[s - 2].rev = BidirectionalPathTracer::pdf(scene, camera, Some(pt), q, qm);
+                }
+            },
+            None => {
+                // The camera subpath ended on a light; pt stands in for a light subpath vertex
+                let pm = pt_minus.unwrap();
+                let hit = pt.hit.as_ref().unwrap();
+                let to_pt = Ray::new(pm.p.copy(), &pt.p - &pm.p);
+                cam[t - 1].rev = scene.light_area_pdf(&to_pt, hit, f);
+
+                let to_pm = (&pm.p - &pt.p).normalize();
+                let as_light = Vertex::new(VertexKind::Light, pt.p.copy(), pt.n.copy(), None, Vector3::new(0.0, 0.0, 0.0), Color::new(0.0, 0.0, 0.0), 0.0);
+                let pdf_dir = (&to_pm * &pt.n).abs() / PI;
+                cam[t - 2].rev = BidirectionalPathTracer::convert_density(pdf_dir, &as_light, pm);
+            }
+        }
+
+        let remap = |p: f32| if p != 0.0 { p } else { 1.0 };
+        let connect_camera = camera.aperture_size() <= 0.0;
+        let mut sum = 0.0;
+
+        let mut ri = 1.0;
+        for i in (1..t).rev() {
+            ri *= remap(cam[i].rev) / remap(cam[i].fwd);
+            if !cam[i].delta && !cam[i - 1].delta && (i > 1 || connect_camera) {
+                sum += ri;
+            }
+        }
+
+        let mut ri = 1.0;
+        for i in (0..s).rev() {
+            ri *= remap(light[i].rev) / remap(light[i].fwd);
+            let delta_before = if i > 0 { light[i - 1].delta } else { false };
+            if !light[i].delta && !delta_before {
+                sum += ri;
+            }
+        }
+
+        return 1.0 / (1.0 + sum);
+    }
+
+    fn splat(&self, pixel: (u64, u64), c: &Color, ray: &Ray) {
+        let c = match ray.get_wavelength() {
+            Some(l) => spectrum::spectral_to_rgb(c.r, l),
+            None => c.copy()
+        };
+        if !(c.r.is_finite() && c.g.is_finite() && c.b.is_finite()) {
+            return;
+        }
+        let index = (pixel.1 * self.width + pixel.0) as usize;
+        let mut image = self.light_image.lock().unwrap();
+        for (sum, value) in image[index].iter_mut().zip([c.r, c.g, c.b]) {
+            *sum = sum.saturating_add((value as f64 * BidirectionalPathTracer::SPLAT_SCALE) as i64);
+        }
+    }
+}
+
+impl Integrator for BidirectionalPathTracer {
+    fn camera_ray_color(&self, scene: &Scene, ray: Ray, f: f32) -> (Color, f32) {
+        if self.max_depth == 0 {
+            return (Color::new(0.0, 0.0, 0.0), 1.0);
+        }
+        let camera = match scene.get_camera(0) {
+            Some(c) => c,
+            None => {
+                return (Color::new(0.0, 0.0, 0.0), 1.0);
+            }
+        };
+        let max_vertices = self.max_depth as usize + 1;
+        let context = Context { scene, camera, f };
+
+        let mut camera_path = vec![Vertex::new(VertexKind::Camera, ray.get_origin().copy(), camera.forward(),
+            None, Vector3::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0), 1.0)];
+        let pdf_dir = camera.pdf_direction(ray.get_direction());
+        let mut radiance = self.random_walk(&context, ray.copy(), Color::new(1.0, 1.0, 1.0), pdf_dir, &mut camera_path, max_vertices);
+
+        if camera_path.len() == 1 && self.transparent {
+            return (Color::new(0.0, 0.0, 0.0), 0.0);
+        }
+        if camera_path.len() > 1 && scene.material(camera_path[1].hit.as_ref().unwrap().material()).is_holdout() {
+            return (Color::new(0.0, 0.0, 0.0), 0.0);
+        }
+
+        let light_path = self.light_subpath(&context, ray.get_wavelength(), max_vertices - 1);
+        let paths = Subpaths { camera: &camera_path, light: &light_path };
+
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                let depth = s + t;
+                if depth < 2 || depth > max_vertices || (s == 1 && t == 1) {
+                    continue;
+                }
+
+                let mut sampled = None;
+                let (c, pixel) = self.connect(&context, &paths, s, t, &mut sampled, &ray);
+                if c.luminance() <= 0.0 {
+                    continue;
+                }
+                let weight = self.mis_weight(&context, &paths, sampled.as_ref(), s, t);
+
+                match pixel {
+                    Some(px) => self.splat(px, &Color::scale(&c, weight), &ray),
+                    None => {
+                        radiance = Color::add(&radiance, &Color::scale(&c, weight));
+                    }
+                }
+            }
+        }
+
+        return (radiance, 1.0);
+    }
+
+    fn light_image(&self) -> Option<Vec<Color>> {
+        let image = self.light_image.lock().unwrap();
+        let scale = 1.0 / BidirectionalPathTracer::SPLAT_SCALE;
+        Some(image.iter().map(|c| {
+            Color::new((c[0] as f64 * scale) as f32, (c[1] as f64 * scale) as f32, (c[2] as f64 * scale) as f32)
+        }).collect())
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{color::Color, ray::Ray, structures::scene::Scene};
+
+use super::integrator::{Integrator, pass_through};
+
+/** Geometry information shown by the debug integrator */
+pub enum DebugView {
+    /** World space normal facing the camera, mapped from [-1, 1] to [0, 1] */
+    Normal,
+    /** Distance to the camera, white at the camera fading to black at `DEPTH_RANGE` */
+    Depth,
+    UV,
+    /** Distinct flat color per material in the library */
+    MaterialId
+}
+
+/** Shows geometric properties of the first visible surface instead of lighting */
+pub struct DebugIntegrator {
+    view: DebugView
+}
+
+impl DebugIntegrator {
+    const DEPTH_RANGE: f32 = 20.0;
+
+    pub fn new(view: DebugView) -> Self {
+        DebugIntegrator {
+            view
+        }
+    }
+
+    /* Spreads material indices over the color cube with an integer hash */
+    fn id_color(index: usize) -> Color {
+        let mut h = (index as u32).wrapping_add(1).wrapping_mul(0x9E3779B1);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x85EBCA77);
+        h ^= h >> 13;
+        Color::new(
+            (h & 0xFF) as f32 / 255.0,
+            ((h >> 8) & 0xFF) as f32 / 255.0,
+            ((h >> 16) & 0xFF) as f32 / 255.0
+        )
+    }
+}
+
+impl Integrator for DebugIntegrator {
+    fn camera_ray_color(&self, scene: &Scene, ray: Ray, f: f32) -> (Color, f32) {
+        let hit = match scene.trace(&ray, 0.0001, 10000.0, f) {
+            Some(h) => h,
+            None => {
+                return (Color::new(0.0, 0.0, 0.0), 0.0);
+            }
+        };
+
+        if scene.material(hit.material()).alpha(&hit) < crate::random::random() {
+            return self.camera_ray_color(scene, pass_through(&ray, &hit), f);
+        }
+
+        let c = match self.view {
+            DebugView::Normal => {
+                let n = hit.n();
+                Color::new(n.x * 0.5 + 0.5, n.y * 0.5 + 0.5, n.z * 0.5 + 0.5)
+            },
+            DebugView::Depth => {
+                let d = hit.t() * ray.get_direction().length();
+                let v = (1.0 - d / DebugIntegrator::DEPTH_RANGE).max(0.0);
+                Color::new(v, v, v)
+            },
+            DebugView::UV => Color::new(hit.uv().x, hit.uv().y, 0.0),
+            DebugView::MaterialId => DebugIntegrator::id_color(hit.material().index())
+        };
+        return (c, 1.0);
+    }
+
+    fn supports_spectral(&self) -> bool {
+        false
+    }
+}
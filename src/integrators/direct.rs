@@ -0,0 +1,127 @@
+use crate::{color::Color, ray::Ray, structures::{scene::Scene, renderable::HitRecord, material::{Material, ScatterResult}}};
+
+use super::integrator::{Integrator, to_ray_spectrum, pass_through, background, transmittance, power_heuristic, sample_lights};
+
+/** Renders only light arriving straight from emissive objects and the environment,
+ * sampling both the lights and the materials and combining them with multiple importance
+ * sampling. Mirrors and glass are followed up to the maximum depth. Participating media
+ * only dim the light passing through them and never scatter it, which makes it suited for
+ * quick previews. */
+pub struct DirectLighting {
+    max_depth: u64,
+    transparent: bool
+}
+
+impl DirectLighting {
+    pub fn new(max_depth: u64, transparent: bool) -> Self {
+        DirectLighting {
+            max_depth, transparent
+        }
+    }
+
+    fn ray_color(&self, scene: &Scene, ray: Ray, depth: u64, camera: bool, f: f32) -> (Color, f32) {
+        if depth == 0 {
+            return (Color::new(0.0, 0.0, 0.0), 1.0);
+        }
+
+        let hit = scene.trace(&ray, 0.0001, 10000.0, f);
+        let tr = transmittance(scene, &ray, hit.as_ref());
+        let (c, alpha) = self.hit_color(scene, ray, hit, depth, camera, f);
+        return (&tr * &c, alpha);
+    }
+
+    /* Light leaving the hit surface (or the environment) towards the ray origin */
+    fn hit_color(&self, scene: &Scene, ray: Ray, hit: Option<HitRecord>, depth: u64, camera: bool, f: f32) -> (Color, f32) {
+        let hit = match hit {
+            Some(h) => h,
+            None => {
+                let alpha = if camera && self.transparent { 0.0 } else { 1.0 };
+                return (Color::scale(&background(scene, &ray), alpha), alpha);
+            }
+        };
+
+        let material = scene.material(hit.material());
+        if material.alpha(&hit) < crate::random::random() {
+            return self.ray_color(scene, pass_through(&ray, &hit), depth, camera, f);
+        }
+
+        if camera && material.is_holdout() {
+            return (Color::new(0.0, 0.0, 0.0), 0.0);
+        }
+
+        if camera && material.is_shadow_catcher() {
+            return (Color::new(0.0, 0.0, 0.0), DirectLighting::shadow_catcher_alpha(scene, &ray, &hit, f));
+        }
+
+        let emitted = to_ray_spectrum(&ray, material.emitted(&hit));
+        let mut scatter = match material.scatter(&ray, &hit) {
+            Some(s) => s,
+            None => {
+                return (emitted, 1.0);
+            }
+        };
+        scatter.ray.set_wavelength(ray.get_wavelength());
+
+        let direct = match DirectLighting::direct_light(scene, &ray, &hit, material, &scatter, f) {
+            Some(c) => c,
+            None => {
+                let attenuation = to_ray_spectrum(&ray, scatter.attenuation);
+                let (c, _) = self.ray_color(scene, scatter.ray, depth - 1, false, f);
+                &attenuation * &c
+            }
+        };
+
+        return (emitted + direct, 1.0);
+    }
+
+    /* Light and environment samples plus the material sample, weighted by the power heuristic.
+     * None for materials that can't be evaluated, whose sample has to be followed instead. */
+    fn direct_light(scene: &Scene, ray: &Ray, hit: &HitRecord, material: &dyn Material,
+            scatter: &ScatterResult, f: f32) -> Option<Color> {
+        let wo = -ray.get_direction().normalize();
+        let wi = scatter.ray.get_direction().normalize();
+        let (_, scatter_pdf) = material.eval(&wo, &wi, hit)?;
+        let mut c = sample_lights(scene, ray, hit, material, f);
+
+        let attenuation = to_ray_spectrum(ray, scatter.attenuation.copy());
+        match scene.trace(&scatter.ray, 0.0001, 10000.0, f) {
+            Some(h) => {
+                let m = scene.material(h.material());
+                if m.is_emissive() {
+                    let le = &to_ray_spectrum(ray, m.emitted(&h)) * &transmittance(scene, &scatter.ray, Some(&h));
+                    let weight = power_heuristic(scatter_pdf, scene.light_pdf(&scatter.ray, &h, f));
+                    c = c + Color::scale(&(&attenuation * &le), weight);
+                }
+            },
+            None => {
+                let le = &background(scene, &scatter.ray) * &transmittance(scene, &scatter.ray, None);
+                let pdf_env = scene.environment().pdf(scatter.ray.get_direction());
+                let weight = power_heuristic(scatter_pdf, pdf_env);
+                c = c + Color::scale(&(&attenuation * &le), weight);
+            }
+        }
+
+        return Some(c);
+    }
+
+    /* Fraction of environment light towards the shadow catcher blocked by other objects */
+    fn shadow_catcher_alpha(scene: &Scene, ray: &Ray, hit: &HitRecord, f: f32) -> f32 {
+        let scatter = match scene.material(hit.material()).scatter(ray, hit) {
+            Some(s) => s,
+            None => {
+                return 0.0;
+            }
+        };
+
+        return match scene.trace(&scatter.ray, 0.0001, 10000.0, f) {
+            Some(h) if !scene.material(h.material()).is_shadow_catcher() => 1.0,
+            _ => 0.0
+        };
+    }
+}
+
+impl Integrator for DirectLighting {
+    fn camera_ray_color(&self, scene: &Scene, ray: Ray, f: f32) -> (Color, f32) {
+        self.ray_color(scene, ray, self.max_depth, true, f)
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{color::Color, ray::Ray, spectrum, utils::GeneralInfo, structures::{scene::Scene, renderable::HitRecord, material::{Material, ScatterResult}, medium::{Medium, MediumSample}}};
+
+use super::{path::PathTracer, bdpt::BidirectionalPathTracer, photon::PhotonMapper, mlt::MetropolisLightTransport, direct::DirectLighting, ambient_occlusion::AmbientOcclusion, debug::{DebugIntegrator, DebugView}};
+
+/** Rendering algorithm turning camera rays into colors */
+pub trait Integrator: Send + Sync {
+    /** Color and alpha seen along a camera ray */
+    fn camera_ray_color(&self, scene: &Scene, ray: Ray, f: f32) -> (Color, f32);
+
+    /** Like `camera_ray_color`, with the light split into direct light (seen by the camera or
+     * reflected once) and indirect light. Integrators that don't tell them apart report all
+     * of it as direct. */
+    fn camera_ray_light(&self, scene: &Scene, ray: Ray, f: f32) -> (Color, Color, f32) {
+        let (c, alpha) = self.camera_ray_color(scene, ray, f);
+        (c, Color::new(0.0, 0.0, 0.0), alpha)
+    }
+
+    /** Called once per frame before rendering starts, e.g. to shoot photons */
+    fn prepare(&mut self, _scene: &Scene, _f: f32) {}
+
+    /** Work done by each render thread before its share of the pixels, e.g. Markov chains */
+    fn render_thread(&self, _scene: &Scene, _thread: u64, _threads: u64, _f: f32) {}
+
+    /** Whether the integrator transports light and can carry single wavelengths in spectral mode */
+    fn supports_spectral(&self) -> bool {
+        true
+    }
+
+    /** Whether the pixels are estimated by their camera samples, so that adaptive sampling
+     * can stop sampling those that converged and progressive renders can add samples in passes */
+    fn supports_adaptive(&self) -> bool {
+        true
+    }
+
+    /** Whether the pixels are sampled through the camera. Integrators whose light image is the
+     * whole picture only need the render threads, unless render passes need camera rays. */
+    fn samples_pixels(&self) -> bool {
+        true
+    }
+
+    /** Light deposited onto arbitrary pixels instead of the one being rendered, e.g. light
+     * subpaths connected to the camera. Summed over all samples, added to the finished image. */
+    fn light_image(&self) -> Option<Vec<Color>> {
+        None
+    }
+
+    /** Number of paths the light image is summed over, given the camera samples taken in all
+     * pixels. They are spread over the whole image, so a pixel receives its share of them. */
+    fn light_paths(&self, camera_samples: u64) -> u64 {
+        camera_samples
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum IntegratorType {
+    Path,
+    Direct,
+    Bidirectional,
+    PhotonMapping,
+    Metropolis,
+    AmbientOcclusion,
+    Normal,
+    Depth,
+    UV,
+    MaterialId
+}
+
+impl IntegratorType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "path" => Some(IntegratorType::Path),
+            "direct" => Some(IntegratorType::Direct),
+            "bdpt" => Some(IntegratorType::Bidirectional),
+            "photon" => Some(IntegratorType::PhotonMapping),
+            "mlt" => Some(IntegratorType::Metropolis),
+            "ao" => Some(IntegratorType::AmbientOcclusion),
+            "normal" => Some(IntegratorType::Normal),
+            "depth" => Some(IntegratorType::Depth),
+            "uv" => Some(IntegratorType::UV),
+            "material" => Some(IntegratorType::MaterialId),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntegratorType::Path => "path",
+            IntegratorType::Direct => "direct",
+            IntegratorType::Bidirectional => "bdpt",
+            IntegratorType::PhotonMapping => "photon",
+            IntegratorType::Metropolis => "mlt",
+            IntegratorType::AmbientOcclusion => "ao",
+            IntegratorType::Normal => "normal",
+            IntegratorType::Depth => "depth",
+            IntegratorType::UV => "uv",
+            IntegratorType::MaterialId => "material"
+        }
+    }
+
+    /** Whether the integrator can render in passes, as a time-limited render does. Metropolis
+     * light transport spreads its mutations over the whole image at once. */
+    pub fn renders_in_passes(&self) -> bool {
+        !matches!(self, IntegratorType::Metropolis)
+    }
+
+    pub fn create(&self, info: &GeneralInfo) -> Box<dyn Integrator> {
+        match self {
+            IntegratorType::Path => {
+                let mut path = PathTracer::new(info.ray_recursion, info.transparent);
+                path.set_bounce_limits(info.bounces);
+                path.set_clamp(info.clamp);
+                Box::new(path)
+            },
+            IntegratorType::Direct => Box::new(DirectLighting::new(info.ray_recursion, info.transparent)),
+            IntegratorType::Bidirectional => Box::new(BidirectionalPathTracer::new(info.ray_recursion, info.transparent,
+                info.out_width, info.out_height)),
+            IntegratorType::PhotonMapping => Box::new(PhotonMapper::new(info.ray_recursion, info.transparent,
+                info.photons as usize, info.photon_passes() as usize, info.threads as usize, info.photon_radius, info.seed)),
+            IntegratorType::Metropolis => {
+                let mut path = PathTracer::new(info.ray_recursion, false);
+                path.set_bounce_limits(info.bounces);
+                Box::new(MetropolisLightTransport::new(path, info.out_width, info.out_height, info.aa_sampling,
+                    info.threads, info.spectral, info.seed))
+            },
+            IntegratorType::AmbientOcclusion => Box::new(AmbientOcclusion::new(1.0)),
+            IntegratorType::Normal => Box::new(DebugIntegrator::new(DebugView::Normal)),
+            IntegratorType::Depth => Box::new(DebugIntegrator::new(DebugView::Depth)),
+            IntegratorType::UV => Box::new(DebugIntegrator::new(DebugView::UV)),
+            IntegratorType::MaterialId => Box::new(DebugIntegrator::new(DebugView::MaterialId))
+        }
+    }
+}
+
+/** In spectral mode colors are reduced to their value at the wavelength carried by the ray */
+pub fn to_ray_spectrum(ray: &Ray, c: Color) -> Color {
+    return match ray.get_wavelength() {
+        Some(l) => spectrum::monochromatic(&c, l),
+        None => c
+    };
+}
+
+/** Attenuation of light carried forwards from the light sources by a scattering event. Camera
+ * paths carry radiance through refraction unchanged, leaving out the squared ratio of the
+ * indices of refraction, so light subpaths and photons take it on to match them. */
+pub fn light_attenuation(ray: &Ray, scatter: &ScatterResult) -> Color {
+    return Color::scale(&to_ray_spectrum(ray, scatter.attenuation.copy()), scatter.eta * scatter.eta);
+}
+
+/** Continues a ray unchanged behind the transparent part of a surface */
+pub fn pass_through(ray: &Ray, hit: &HitRecord) -> Ray {
+    let d = ray.get_direction().normalize();
+    let p = hit.p() + &(&d * 0.0001);
+    let mut continued = Ray::new(p, d);
+    continued.set_wavelength(ray.get_wavelength());
+    return continued;
+}
+
+/** Samples the medium the ray travelled through on its way to the hit, if there is one */
+pub fn sample_medium<'a>(scene: &'a Scene, ray: &Ray, hit: Option<&HitRecord>) -> Option<(&'a dyn Medium, MediumSample)> {
+    let medium = scene.segment_medium(hit)?;
+    let t_max = match hit {
+        Some(h) => h.t(),
+        None => 10000.0
+    };
+    return Some((medium, medium.sample(ray, t_max)));
+}
+
+/** Fraction of light passing unscattered through the medium the ray travelled through on its
+ * way to the hit, for integrators that let media dim light without scattering it */
+pub fn transmittance(scene: &Scene, ray: &Ray, hit: Option<&HitRecord>) -> Color {
+    let medium = match scene.segment_medium(hit) {
+        Some(m) => m,
+        None => {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+    };
+    let t_max = match hit {
+        Some(h) => h.t(),
+        None => 10000.0
+    };
+    return to_ray_spectrum(ray, medium.transmittance(ray, t_max));
+}
+
+pub fn background(scene: &Scene, ray: &Ray) -> Color {
+    let background = scene.environment().color(ray.get_direction());
+    return to_ray_spectrum(ray, background);
+}
+
+/** Multiple importance sampling weight of a strategy with pdf `a` against one with pdf `b` */
+pub fn power_heuristic(a: f32, b: f32) -> f32 {
+    let a2 = a * a;
+    let b2 = b * b;
+    if a2 + b2 <= 0.0 {
+        return 0.0;
+    }
+    return a2 / (a2 + b2);
+}
+
+/** Next event estimation at a surface that can be evaluated: one sample on an emissive object
+ * and one towards the environment, each weighted by the power heuristic against the
+ * material's own sampling and dimmed by the media on the way. The material sample has to be
+ * accounted for by the caller. */
+pub fn sample_lights(scene: &Scene, ray: &Ray, hit: &HitRecord, material: &dyn Material, f: f32) -> Color {
+    let wo = -ray.get_direction().normalize();
+    let origin = hit.p() + &(hit.n() * 0.0001);
+    let mut c = Color::new(0.0, 0.0, 0.0);
+
+    if let Some((light, pdf_area)) = scene.sample_light(f) {
+        let to_light = light.p() - &origin;
+        let dist = to_light.length();
+        let wi = &to_light * (1.0 / dist);
+        let cos_light = (&wi * light.n()).abs();
+
+        if let Some((f_cos, pdf)) = material.eval(&wo, &wi, hit) {
+            let mut shadow_ray = Ray::new(origin.copy(), wi.copy());
+            shadow_ray.set_wavelength(ray.get_wavelength());
+            let seen = scene.trace(&shadow_ray, 0.0001, dist * 1.001, f);
+            if let Some(h) = seen {
+                if h.t() > dist * 0.999 && cos_light > 0.0 && f_cos.luminance() > 0.0 {
+                    let le = &to_ray_spectrum(ray, scene.material(h.material()).emitted(&h)) * &transmittance(scene, &shadow_ray, Some(&h));
+                    let pdf_light = pdf_area * dist * dist / cos_light;
+                    let weight = power_heuristic(pdf_light, pdf) / pdf_light;
+                    c = c + Color::scale(&(&to_ray_spectrum(ray, f_cos) * &le), weight);
+                }
+            }
+        }
+    }
+
+    let (wi, pdf_env) = scene.environment().sample();
+    if let Some((f_cos, pdf)) = material.eval(&wo, &wi, hit) {
+        let mut shadow_ray = Ray::new(origin.copy(), wi);
+        shadow_ray.set_wavelength(ray.get_wavelength());
+        if pdf_env > 0.0 && f_cos.luminance() > 0.0 && scene.trace(&shadow_ray, 0.0001, 10000.0, f).is_none() {
+            let le = &background(scene, &shadow_ray) * &transmittance(scene, &shadow_ray, None);
+            let weight = power_heuristic(pdf_env, pdf) / pdf_env;
+            c = c + Color::scale(&(&to_ray_spectrum(ray, f_cos) * &le), weight);
+        }
+    }
+
+    return c;
+}
@@ -0,0 +1,361 @@
+use std::{cell::RefCell, rc::Rc, sync::{Mutex, Condvar}};
+
+use crossbeam::thread;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rand_distr::StandardNormal;
+
+use crate::{color::Color, ray::Ray, spectrum, random, stats, samplers::sampler::{self, Sampler}, structures::scene::Scene};
+
+use super::{integrator::Integrator, path::PathTracer};
+
+/* One coordinate of the primary sample space, with the state before the current mutation */
+#[derive(Clone, Copy)]
+struct PrimarySample {
+    value: f32,
+    modified: u64,
+    value_backup: f32,
+    modified_backup: u64
+}
+
+/* Primary sample vector of a Markov chain. Its coordinates are created and mutated lazily,
+ * when the path being traced asks for them. */
+struct MltSampler {
+    samples: Vec<PrimarySample>,
+    rng: StdRng,
+    iteration: u64,
+    last_large_step: u64,
+    large_step: bool,
+    index: usize
+}
+
+impl MltSampler {
+    /* Standard deviation of the small step mutations */
+    const SIGMA: f32 = 0.01;
+    const LARGE_STEP_PROBABILITY: f32 = 0.3;
+
+    /* The first iteration is a large step, so equal seeds produce equal first paths */
+    fn new(seed: u64) -> Self {
+        MltSampler {
+            samples: Vec::new(),
+            rng: StdRng::seed_from_u64(seed),
+            iteration: 0,
+            last_large_step: 0,
+            large_step: true,
+            index: 0
+        }
+    }
+
+    fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.gen::<f32>() < MltSampler::LARGE_STEP_PROBABILITY;
+        self.index = 0;
+    }
+
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    fn reject(&mut self) {
+        for s in self.samples.iter_mut() {
+            if s.modified == self.iteration {
+                s.value = s.value_backup;
+                s.modified = s.modified_backup;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    fn next(&mut self) -> f32 {
+        if self.index >= self.samples.len() {
+            self.samples.push(PrimarySample {
+                value: 0.0, modified: 0, value_backup: 0.0, modified_backup: 0
+            });
+        }
+
+        let rng = &mut self.rng;
+        let s = &mut self.samples[self.index];
+        self.index += 1;
+
+        // A coordinate not used since the last accepted large step has to catch up with it
+        if s.modified < self.last_large_step {
+            s.value = rng.gen();
+            s.modified = self.last_large_step;
+        }
+
+        s.value_backup = s.value;
+        s.modified_backup = s.modified;
+        if self.large_step {
+            s.value = rng.gen();
+        } else {
+            // Small steps skipped while the coordinate was unused are applied all at once
+            let steps = (self.iteration - s.modified) as f32;
+            let normal: f32 = rng.sample(StandardNormal);
+            s.value += normal * MltSampler::SIGMA * steps.sqrt();
+            s.value -= s.value.floor();
+            if s.value >= 1.0 {
+                s.value = 0.0;
+            }
+        }
+        s.modified = self.iteration;
+
+        return s.value;
+    }
+}
+
+/* Hands the chain's primary samples to `random` while a path is traced */
+struct SharedSampler(Rc<RefCell<MltSampler>>);
+
+impl Sampler for SharedSampler {
+    fn next(&mut self) -> f32 {
+        self.0.borrow_mut().next()
+    }
+}
+
+/** Primary sample space Metropolis light transport (Kelemen et al.).
+ *
+ * Paths are traced by the path tracer, but every random number it draws comes from a primary
+ * sample vector. Markov chains mutate that vector, either by small perturbations that explore
+ * the neighbourhood of a bright path or by large steps that replace it, and accept the new
+ * path with a probability given by the ratio of the luminances. Hard to find light paths,
+ * e.g. through a keyhole, are explored once found instead of found again for every pixel.
+ *
+ * A bootstrap phase traces independent paths to estimate the overall image brightness and to
+ * pick the starting points of the chains. The chains then run on the render threads and
+ * splat their paths onto the image. Their number and seeds don't depend on the threads, and
+ * they are added to the image in order, so the result only depends on the seed. */
+pub struct MetropolisLightTransport {
+    path: PathTracer,
+    width: u64,
+    height: u64,
+    mutations_per_pixel: u64,
+    threads: u64,
+    spectral: bool,
+    /* Cumulative luminance of the bootstrap paths */
+    bootstrap_cdf: Vec<f32>,
+    normalization: f32,
+    seed: u64,
+    /* Splatted image and the number of chains added to it */
+    image: Mutex<(Vec<Color>, u64)>,
+    merged: Condvar
+}
+
+impl MetropolisLightTransport {
+    const BOOTSTRAP_SAMPLES: u64 = 100000;
+    const CHAINS: u64 = 64;
+
+    /** Runs chains of paths traced by the given path tracer */
+    pub fn new(path: PathTracer, width: u64, height: u64, mutations_per_pixel: u64,
+        threads: u64, spectral: bool, seed: u64) -> Self {
+        let mut image = Vec::new();
+        for _ in 0..width*height {
+            image.push(Color::new(0.0, 0.0, 0.0));
+        }
+
+        MetropolisLightTransport {
+            path,
+            width,
+            height,
+            mutations_per_pixel,
+            threads: threads.max(1),
+            spectral,
+            bootstrap_cdf: Vec::new(),
+            normalization: 0.0,
+            seed,
+            image: Mutex::new((image, 0)),
+            merged: Condvar::new()
+        }
+    }
+
+    /* Seed of the i-th stream of random numbers of a frame */
+    fn stream_seed(&self, i: u64, f: f32) -> u64 {
+        sampler::frame_seed(self.seed, f as u64).wrapping_add(i)
+    }
+
+    /* Traces the path described by the sampler's primary samples, returns its color and pixel */
+    fn evaluate(&self, scene: &Scene, sampler: &Rc<RefCell<MltSampler>>, f: f32) -> (Color, usize) {
+        let camera = match scene.get_camera(0) {
+            Some(c) => c,
+            None => {
+                return (Color::new(0.0, 0.0, 0.0), 0);
+            }
+        };
+
+        random::set_sampler(Some(Box::new(SharedSampler(sampler.clone()))));
+
+        let x = ((random::random() * self.width as f32) as u64).min(self.width - 1);
+        let y = ((random::random() * self.height as f32) as u64).min(self.height - 1);
+        let mut ray = camera.get_ray(x, y, self.width, self.height);
+        let color = if self.spectral {
+            let wavelength = spectrum::sample_wavelength();
+            ray.set_wavelength(Some(wavelength));
+            let (value, _) = self.path.camera_ray_color(scene, ray, f);
+            spectrum::spectral_to_rgb(value.r, wavelength)
+        } else {
+            self.path.camera_ray_color(scene, ray, f).0
+        };
+
+        random::set_sampler(None);
+
+        return (color, (y * self.width + x) as usize);
+    }
+
+    /* Scalar contribution that the chains sample proportionally to */
+    fn contribution(c: &Color) -> f32 {
+        let l = c.luminance();
+        if l.is_finite() && l > 0.0 {
+            l
+        } else {
+            0.0
+        }
+    }
+
+    /* Runs one Markov chain, splatting onto the given image */
+    fn run_chain(&self, scene: &Scene, chain: u64, image: &mut [Color], f: f32) {
+        let chains = MetropolisLightTransport::CHAINS;
+        let total = self.mutations_per_pixel * self.width * self.height;
+        let mutations = total / chains + if chain < total % chains { 1 } else { 0 };
+
+        // Start from a bootstrap path, picked proportionally to its contribution
+        let mut rng = StdRng::seed_from_u64(self.stream_seed(MetropolisLightTransport::BOOTSTRAP_SAMPLES + chain, f));
+        let target = rng.gen::<f32>() * self.normalization * self.bootstrap_cdf.len() as f32;
+        let start = self.bootstrap_cdf.partition_point(|c| *c <= target).min(self.bootstrap_cdf.len() - 1);
+
+        let sampler = Rc::new(RefCell::new(MltSampler::new(self.stream_seed(start as u64, f))));
+        let (mut current, mut current_pixel) = self.evaluate(scene, &sampler, f);
+        let mut current_contribution = MetropolisLightTransport::contribution(&current);
+
+        for _ in 0..mutations {
+            sampler.borrow_mut().start_iteration();
+            let large_step = sampler.borrow().large_step;
+
+            let (proposed, proposed_pixel) = self.evaluate(scene, &sampler, f);
+            let proposed_contribution = MetropolisLightTransport::contribution(&proposed);
+            let accept = if current_contribution > 0.0 {
+                (proposed_contribution / current_contribution).min(1.0)
+            } else {
+                1.0
+            };
+
+            // Both paths are splatted with their expected weights, which lowers the variance
+            if proposed_contribution > 0.0 {
+                let c = Color::scale(&proposed, accept / proposed_contribution);
+                image[proposed_pixel] = Color::add(&image[proposed_pixel], &c);
+            }
+            if current_contribution > 0.0 {
+                let c = Color::scale(&current, (1.0 - accept) / current_contribution);
+                image[current_pixel] = Color::add(&image[current_pixel], &c);
+            }
+
+            let accepted = rng.gen::<f32>() < accept;
+            if accepted {
+                current = proposed;
+                current_pixel = proposed_pixel;
+                current_contribution = proposed_contribution;
+                sampler.borrow_mut().accept();
+            } else {
+                sampler.borrow_mut().reject();
+            }
+            stats::add_mutation(large_step, accepted);
+        }
+    }
+}
+
+impl Integrator for MetropolisLightTransport {
+    fn prepare(&mut self, scene: &Scene, f: f32) {
+        let this = &*self;
+        let contributions = thread::scope(|s| {
+            let handles: Vec<_> = (0..this.threads).map(|t| {
+                s.spawn(move |_| {
+                    let mut part = Vec::new();
+                    let mut i = t;
+                    while i < MetropolisLightTransport::BOOTSTRAP_SAMPLES {
+                        let sampler = Rc::new(RefCell::new(MltSampler::new(this.stream_seed(i, f))));
+                        let (c, _) = this.evaluate(scene, &sampler, f);
+                        part.push((i, MetropolisLightTransport::contribution(&c)));
+                        i += this.threads;
+                    }
+                    part
+                })
+            }).collect();
+
+            let mut contributions = vec![0.0; MetropolisLightTransport::BOOTSTRAP_SAMPLES as usize];
+            for h in handles {
+                for (i, c) in h.join().unwrap() {
+                    contributions[i as usize] = c;
+                }
+            }
+            contributions
+        }).unwrap();
+
+        let mut sum = 0.0;
+        self.bootstrap_cdf = contributions.iter().map(|c| {
+            sum += c;
+            sum
+        }).collect();
+        self.normalization = sum / MetropolisLightTransport::BOOTSTRAP_SAMPLES as f32;
+    }
+
+    /* All light is splatted by the chains */
+    fn camera_ray_color(&self, _scene: &Scene, _ray: Ray, _f: f32) -> (Color, f32) {
+        (Color::new(0.0, 0.0, 0.0), 1.0)
+    }
+
+    fn render_thread(&self, scene: &Scene, thread: u64, threads: u64, f: f32) {
+        if self.normalization <= 0.0 {
+            return;
+        }
+
+        // One image per thread, cleared after each of its chains is merged
+        let mut image = Vec::new();
+        for _ in 0..self.width*self.height {
+            image.push(Color::new(0.0, 0.0, 0.0));
+        }
+
+        let mut chain = thread;
+        while chain < MetropolisLightTransport::CHAINS {
+            self.run_chain(scene, chain, &mut image, f);
+
+            // Floating point sums depend on their order, so chains wait for the previous ones
+            let mut shared = self.image.lock().unwrap();
+            while shared.1 < chain {
+                shared = self.merged.wait(shared).unwrap();
+            }
+            for (sum, c) in shared.0.iter_mut().zip(image.iter_mut()) {
+                *sum = Color::add(sum, c);
+                *c = Color::new(0.0, 0.0, 0.0);
+            }
+            shared.1 += 1;
+            self.merged.notify_all();
+            drop(shared);
+
+            chain += threads.max(1);
+        }
+    }
+
+    fn supports_spectral(&self) -> bool {
+        false
+    }
+
+    /* The chains decide where the samples go */
+    fn supports_adaptive(&self) -> bool {
+        false
+    }
+
+    /* The light image is the whole picture */
+    fn samples_pixels(&self) -> bool {
+        false
+    }
+
+    /* Each mutation splats a total luminance of one, the bootstrap estimate turns that into radiance */
+    fn light_image(&self) -> Option<Vec<Color>> {
+        let image = self.image.lock().unwrap();
+        return Some(image.0.iter().map(|c| Color::scale(c, self.normalization)).collect());
+    }
+
+    /* The chains explore the whole image however many pixels the camera samples */
+    fn light_paths(&self, _camera_samples: u64) -> u64 {
+        self.mutations_per_pixel * self.width * self.height
+    }
+}
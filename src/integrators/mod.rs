@@ -0,0 +1,9 @@
+pub mod integrator;
+pub mod path;
+pub mod direct;
+pub mod bdpt;
+pub mod photon;
+pub mod photon_map;
+pub mod mlt;
+pub mod ambient_occlusion;
+pub mod debug;
@@ -0,0 +1,286 @@
+use crate::{color::Color, ray::Ray, structures::{scene::Scene, renderable::HitRecord, medium::MediumSample, material::Lobe}};
+
+use super::integrator::{Integrator, to_ray_spectrum, pass_through, sample_medium, background};
+
+/** Maximum number of bounces of each kind a path may take, on top of the total depth */
+#[derive(Clone, Copy)]
+pub struct BounceLimits {
+    pub diffuse: u64,
+    pub glossy: u64,
+    pub transmission: u64,
+    pub volume: u64
+}
+
+impl BounceLimits {
+    pub fn unlimited() -> Self {
+        BounceLimits {
+            diffuse: u64::MAX,
+            glossy: u64::MAX,
+            transmission: u64::MAX,
+            volume: u64::MAX
+        }
+    }
+}
+
+/* Bounces taken so far along a path */
+#[derive(Clone, Copy)]
+struct BounceCounts {
+    total: u64,
+    diffuse: u64,
+    glossy: u64,
+    transmission: u64,
+    volume: u64
+}
+
+impl BounceCounts {
+    fn new() -> Self {
+        BounceCounts {
+            total: 0, diffuse: 0, glossy: 0, transmission: 0, volume: 0
+        }
+    }
+
+    /* Records a bounce, returns false if it exceeds one of the limits */
+    fn add(&mut self, lobe: Lobe, max_depth: u64, limits: &BounceLimits) -> bool {
+        self.total += 1;
+        let (count, limit) = match lobe {
+            Lobe::Diffuse => (&mut self.diffuse, limits.diffuse),
+            Lobe::Glossy => (&mut self.glossy, limits.glossy),
+            Lobe::Transmission => (&mut self.transmission, limits.transmission),
+            Lobe::Volume => (&mut self.volume, limits.volume)
+        };
+        *count += 1;
+        return self.total < max_depth && *count <= limit;
+    }
+}
+
+/** Unidirectional path tracer following the sampled scattering direction at every bounce.
+ * Paths are traced iteratively while tracking their throughput, and are terminated with
+ * Russian roulette once they are a few bounces long. */
+pub struct PathTracer {
+    max_depth: u64,
+    limits: BounceLimits,
+    clamp: f32,
+    transparent: bool
+}
+
+impl PathTracer {
+    /* Bounces after which paths become candidates for Russian roulette */
+    const ROULETTE_DEPTH: u64 = 3;
+
+    pub fn new(max_depth: u64, transparent: bool) -> Self {
+        PathTracer {
+            max_depth,
+            limits: BounceLimits::unlimited(),
+            clamp: 0.0,
+            transparent
+        }
+    }
+
+    pub fn set_bounce_limits(&mut self, limits: BounceLimits) {
+        self.limits = limits;
+    }
+
+    /** Upper bound of the luminance of light picked up after the first bounce, 0.0 disables
+     * clamping. Removes fireflies at the cost of losing some energy. */
+    pub fn set_clamp(&mut self, clamp: f32) {
+        self.clamp = clamp.max(0.0);
+    }
+
+    /* Adds light reaching the camera through the path throughput. Light picked up before
+     * the second bounce is direct, the rest indirect. */
+    fn contribute(&self, radiance: &mut (Color, Color), throughput: &Color, light: Color, bounces: u64) {
+        let mut c = throughput * &light;
+        if bounces > 0 && self.clamp > 0.0 {
+            let l = c.luminance();
+            if l > self.clamp {
+                c = c * (self.clamp / l);
+            }
+        }
+        if bounces <= 1 {
+            radiance.0 = Color::add(&radiance.0, &c);
+        } else {
+            radiance.1 = Color::add(&radiance.1, &c);
+        }
+    }
+
+    /* Decides whether the path survives, scaling the throughput to keep the estimate unbiased */
+    fn roulette(throughput: &mut Color, counts: &BounceCounts) -> bool {
+        if counts.total < PathTracer::ROULETTE_DEPTH {
+            return true;
+        }
+
+        let q = throughput.r.max(throughput.g).max(throughput.b).min(0.95);
+        if q <= 0.0 || crate::random::random() >= q {
+            return false;
+        }
+        *throughput = Color::scale(throughput, 1.0 / q);
+        return true;
+    }
+
+    /* Direct light, indirect light and alpha along a path starting with the given ray. Camera
+     * paths are affected by holdouts, shadow catchers and a transparent background. */
+    fn trace_path(&self, scene: &Scene, ray: Ray, mut counts: BounceCounts, camera: bool, f: f32) -> (Color, Color, f32) {
+        let mut ray = ray;
+        let mut radiance = (Color::new(0.0, 0.0, 0.0), Color::new(0.0, 0.0, 0.0));
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut camera = camera;
+
+        loop {
+            let bounces = counts.total;
+            let trace_res = scene.trace(&ray, 0.0001, 10000.0, f);
+
+            match sample_medium(scene, &ray, trace_res.as_ref()) {
+                Some((medium, MediumSample::Scattered { p, weight, emission })) => {
+                    self.contribute(&mut radiance, &throughput, to_ray_spectrum(&ray, emission), bounces);
+                    throughput = &throughput * &to_ray_spectrum(&ray, weight);
+                    if !counts.add(Lobe::Volume, self.max_depth, &self.limits) || !PathTracer::roulette(&mut throughput, &counts) {
+                        break;
+                    }
+
+                    let dir = medium.phase().sample(ray.get_direction());
+                    let wavelength = ray.get_wavelength();
+                    ray = Ray::new(p, dir);
+                    ray.set_wavelength(wavelength);
+                    camera = false;
+                    continue;
+                },
+                Some((_, MediumSample::Passed { weight, emission })) => {
+                    self.contribute(&mut radiance, &throughput, to_ray_spectrum(&ray, emission), bounces);
+                    throughput = &throughput * &to_ray_spectrum(&ray, weight);
+                },
+                None => {}
+            }
+
+            let hit = match trace_res {
+                Some(h) => h,
+                None => {
+                    if camera && self.transparent {
+                        return (radiance.0, radiance.1, 0.0);
+                    }
+                    self.contribute(&mut radiance, &throughput, background(scene, &ray), bounces);
+                    break;
+                }
+            };
+
+            let material = scene.material(hit.material());
+            if material.alpha(&hit) < crate::random::random() {
+                ray = pass_through(&ray, &hit);
+                continue;
+            }
+
+            if camera && material.is_holdout() {
+                return (radiance.0, radiance.1, 0.0);
+            }
+
+            if camera && material.is_shadow_catcher() {
+                return (radiance.0, radiance.1, self.shadow_catcher_alpha(scene, &ray, &hit, f));
+            }
+
+            self.contribute(&mut radiance, &throughput, to_ray_spectrum(&ray, material.emitted(&hit)), bounces);
+
+            let mut scatter = match material.scatter(&ray, &hit) {
+                Some(s) => s,
+                None => break
+            };
+            throughput = &throughput * &to_ray_spectrum(&ray, scatter.attenuation);
+            if !counts.add(scatter.lobe, self.max_depth, &self.limits) || !PathTracer::roulette(&mut throughput, &counts) {
+                break;
+            }
+
+            scatter.ray.set_wavelength(ray.get_wavelength());
+            ray = scatter.ray;
+            camera = false;
+        }
+
+        return (radiance.0, radiance.1, 1.0);
+    }
+
+    /* Fraction of the light reaching the shadow catcher that is blocked by other objects */
+    fn shadow_catcher_alpha(&self, scene: &Scene, ray: &Ray, hit: &HitRecord, f: f32) -> f32 {
+        let mut scatter = match scene.material(hit.material()).scatter(ray, hit) {
+            Some(s) => s,
+            None => {
+                return 0.0;
+            }
+        };
+        scatter.ray.set_wavelength(ray.get_wavelength());
+
+        let mut counts = BounceCounts::new();
+        if !counts.add(scatter.lobe, self.max_depth, &self.limits) {
+            return 0.0;
+        }
+
+        // Shadow catchers never shadow each other, they all stand in for the same photographed ground
+        let blocker = match scene.trace(&scatter.ray, 0.0001, 10000.0, f) {
+            Some(h) => h,
+            None => {
+                return 0.0;
+            }
+        };
+        if scene.material(blocker.material()).is_shadow_catcher() {
+            return 0.0;
+        }
+
+        // Light that would have come from the environment against the light that did, both at
+        // the wavelength of the ray in spectral mode
+        let unoccluded = background(scene, &scatter.ray).luminance();
+        if unoccluded <= 0.0 {
+            return 0.0;
+        }
+        let (direct, indirect, _) = self.trace_path(scene, scatter.ray, counts, false, f);
+        let lit = Color::add(&direct, &indirect);
+
+        return (1.0 - lit.luminance() / unoccluded).clamp(0.0, 1.0);
+    }
+}
+
+impl Integrator for PathTracer {
+    fn camera_ray_color(&self, scene: &Scene, ray: Ray, f: f32) -> (Color, f32) {
+        let (direct, indirect, alpha) = self.camera_ray_light(scene, ray, f);
+        (Color::add(&direct, &indirect), alpha)
+    }
+
+    fn camera_ray_light(&self, scene: &Scene, ray: Ray, f: f32) -> (Color, Color, f32) {
+        if self.max_depth == 0 {
+            return (Color::new(0.0, 0.0, 0.0), Color::new(0.0, 0.0, 0.0), 1.0);
+        }
+        self.trace_path(scene, ray, BounceCounts::new(), true, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::vector3::Vector3, scenes::SceneType, spectrum};
+
+    /* Average alpha of camera rays looking straight down at the ground of the catcher scene */
+    fn catcher_alpha(x: f32, z: f32, spectral: bool) -> f32 {
+        let mut scene = SceneType::Catcher.build(None).unwrap();
+        scene.update_transforms(1.0);
+        let path = PathTracer::new(10, true);
+
+        let n = 4000;
+        let mut alpha = 0.0;
+        for _ in 0..n {
+            let mut ray = Ray::new(Vector3::new(x, 0.0, z), Vector3::new(0.0, -1.0, 0.0));
+            if spectral {
+                ray.set_wavelength(Some(spectrum::sample_wavelength()));
+            }
+            alpha += path.camera_ray_color(&scene, ray, 1.0).1;
+        }
+        return alpha / n as f32;
+    }
+
+    #[test]
+    fn shadow_catcher_alpha_is_the_same_in_spectral_mode() {
+        // Far from the spheres nothing is blocked, next to the ball much of the sky is
+        let open = catcher_alpha(40.0, 40.0, false);
+        let open_spectral = catcher_alpha(40.0, 40.0, true);
+        assert!(open < 0.01 && open_spectral < 0.01);
+
+        let shadow = catcher_alpha(0.0, -0.4, false);
+        let shadow_spectral = catcher_alpha(0.0, -0.4, true);
+        assert!(shadow > 0.2);
+        assert!((shadow_spectral - shadow).abs() < 0.05);
+    }
+}
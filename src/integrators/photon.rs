@@ -0,0 +1,285 @@
+use std::f32::consts::PI;
+
+use crossbeam::thread;
+
+use crate::{color::Color, math::vector3::Vector3, ray::Ray, samplers::{sampler, independent::Independent}, structures::{scene::Scene, renderable::HitRecord, material::Material}};
+
+use super::{integrator::{Integrator, to_ray_spectrum, light_attenuation, pass_through, background, transmittance, power_heuristic, sample_lights}, photon_map::{Photon, PhotonMap}};
+
+/** Path tracer with next event estimation whose caustics come from photon maps.
+ *
+ * Before rendering, photons are shot from emissive objects and stored where they land on
+ * a diffuse surface after passing through mirrors or glass (light - specular - diffuse paths).
+ * The camera paths look those up at every diffuse vertex instead of hoping to hit the light
+ * through the same specular chain. Following probabilistic progressive photon mapping
+ * (Knaus and Zwicker), several independent photon maps are built with shrinking lookup
+ * radii and every camera sample uses one of them at random.
+ *
+ * Photons are only emitted by emissive objects, so caustics of the environment are still
+ * path traced. Photons carry RGB light refracted at the reference wavelength of dispersive
+ * glass, so caustics show no dispersion, even in spectral mode. Participating media dim the
+ * light along camera paths but don't scatter it, and photons pass through them unchanged. */
+pub struct PhotonMapper {
+    max_depth: u64,
+    transparent: bool,
+    photons_per_pass: usize,
+    passes: usize,
+    threads: usize,
+    radius: f32,
+    seed: u64,
+    maps: Vec<PhotonMap>
+}
+
+impl PhotonMapper {
+    /* Radius reduction parameter of progressive photon mapping */
+    const ALPHA: f32 = 2.0 / 3.0;
+    const ROULETTE_DEPTH: u64 = 3;
+
+    pub fn new(max_depth: u64, transparent: bool, photons_per_pass: usize, passes: usize, threads: usize, radius: f32, seed: u64) -> Self {
+        PhotonMapper {
+            max_depth,
+            transparent,
+            photons_per_pass,
+            passes: passes.max(1),
+            threads: threads.max(1),
+            radius,
+            seed,
+            maps: Vec::new()
+        }
+    }
+
+    /* Lookup radius of the photon map built in the given pass, starting at 0 */
+    fn pass_radius(&self, pass: usize) -> f32 {
+        let mut r2 = self.radius * self.radius;
+        for i in 1..=pass {
+            r2 *= (i as f32 + PhotonMapper::ALPHA) / (i as f32 + 1.0);
+        }
+        r2.sqrt()
+    }
+
+    /* Traces photons from the lights, keeping those that reach a diffuse surface through specular bounces.
+     * Every photon of a pass draws its own stream of random numbers, so the maps don't depend on the threads. */
+    fn trace_photons(&self, scene: &Scene, pass: usize, count: usize, f: f32) -> Vec<Photon> {
+        let mut photons = Vec::new();
+
+        for i in 0..count {
+            crate::random::start_pixel_sample(pass as u64, 0, i as u64);
+            let (light, pdf_area) = match scene.sample_light(f) {
+                Some(l) => l,
+                None => {
+                    return photons;
+                }
+            };
+
+            let n = light.n().copy();
+            let le = scene.material(light.material()).emitted(&light);
+            let mut dir = &n + &Vector3::random_in_unit_sphere();
+            if dir.near_zero() {
+                dir = n.copy();
+            }
+
+            // Cosine weighted emission, the cosine and the pdf cancel out except for PI
+            let mut flux = Color::scale(&le, PI / (pdf_area * count as f32));
+            let initial = flux.r.max(flux.g).max(flux.b);
+            let mut ray = Ray::new(light.p().copy(), dir.normalize());
+            let mut specular = false;
+            let mut depth = 0;
+
+            while depth < self.max_depth {
+                let hit = match scene.trace(&ray, 0.0001, 10000.0, f) {
+                    Some(h) => h,
+                    None => break
+                };
+
+                let material = scene.material(hit.material());
+                if material.alpha(&hit) < crate::random::random() {
+                    ray = pass_through(&ray, &hit);
+                    continue;
+                }
+
+                let wi = -ray.get_direction().normalize();
+                let scatter = match material.scatter(&ray, &hit) {
+                    Some(s) => s,
+                    None => break
+                };
+
+                let out = scatter.ray.get_direction().normalize();
+                if material.eval(&wi, &out, &hit).is_some() {
+                    // Diffuse surface: a caustic if the photon came through specular bounces,
+                    // otherwise direct light that next event estimation already handles
+                    if specular {
+                        photons.push(Photon {
+                            p: hit.p().copy(),
+                            wi,
+                            n: hit.n().copy(),
+                            flux
+                        });
+                    }
+                    break;
+                }
+
+                specular = true;
+                flux = &flux * &light_attenuation(&ray, &scatter);
+                depth += 1;
+                if depth >= PhotonMapper::ROULETTE_DEPTH {
+                    let q = (flux.r.max(flux.g).max(flux.b) / initial).min(0.95);
+                    if initial <= 0.0 || q <= 0.0 || crate::random::random() >= q {
+                        break;
+                    }
+                    flux = Color::scale(&flux, 1.0 / q);
+                }
+                ray = scatter.ray;
+            }
+        }
+
+        return photons;
+    }
+
+    /* Reflected caustic radiance at a diffuse hit, estimated from the photon density around it */
+    fn caustics(&self, map: &PhotonMap, ray: &Ray, hit: &HitRecord, material: &dyn Material) -> Color {
+        let wo = -ray.get_direction().normalize();
+        let n = hit.n();
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+
+        map.for_each_near(hit.p(), |photon| {
+            if &photon.n * n < 0.5 {
+                return;
+            }
+            let cos = &photon.wi * n;
+            if cos <= 0.0 {
+                return;
+            }
+            if let Some((f_cos, _)) = material.eval(&wo, &photon.wi, hit) {
+                sum = Color::add(&sum, &(&Color::scale(&f_cos, 1.0 / cos) * &photon.flux));
+            }
+        });
+
+        let r = map.radius();
+        return to_ray_spectrum(ray, Color::scale(&sum, 1.0 / (PI * r * r)));
+    }
+}
+
+impl Integrator for PhotonMapper {
+    fn prepare(&mut self, scene: &Scene, f: f32) {
+        // Every thread builds the maps of every so many passes
+        let this = &*self;
+        let threads = this.threads.min(this.passes);
+        let mut maps = thread::scope(|s| {
+            let handles: Vec<_> = (0..threads).map(|first| {
+                s.spawn(move |_| {
+                    crate::random::set_sampler(Some(Box::new(Independent::new(sampler::frame_seed(this.seed, f as u64)))));
+                    let maps: Vec<(usize, PhotonMap)> = (first..this.passes).step_by(threads).map(|pass| {
+                        let photons = this.trace_photons(scene, pass, this.photons_per_pass, f);
+                        (pass, PhotonMap::new(photons, this.pass_radius(pass)))
+                    }).collect();
+                    crate::random::set_sampler(None);
+                    maps
+                })
+            }).collect();
+            handles.into_iter().flat_map(|h| h.join().unwrap()).collect::<Vec<(usize, PhotonMap)>>()
+        }).unwrap();
+
+        maps.sort_by_key(|(pass, _)| *pass);
+        self.maps = maps.into_iter().map(|(_, map)| map).collect();
+    }
+
+    fn camera_ray_color(&self, scene: &Scene, ray: Ray, f: f32) -> (Color, f32) {
+        let map = if self.maps.is_empty() {
+            None
+        } else {
+            let index = ((crate::random::random() * self.maps.len() as f32) as usize).min(self.maps.len() - 1);
+            Some(&self.maps[index])
+        };
+
+        let mut ray = ray;
+        let mut radiance = Color::new(0.0, 0.0, 0.0);
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut depth = 0;
+        // Whether the path went through a diffuse vertex, and only specular ones since then
+        let mut after_diffuse = false;
+        let mut specular_since = false;
+        let mut last_pdf = 0.0;
+
+        while depth < self.max_depth {
+            let trace_res = scene.trace(&ray, 0.0001, 10000.0, f);
+            throughput = &throughput * &transmittance(scene, &ray, trace_res.as_ref());
+            let hit = match trace_res {
+                Some(h) => h,
+                None => {
+                    if depth == 0 && self.transparent {
+                        return (radiance, 0.0);
+                    }
+                    let weight = if after_diffuse && !specular_since {
+                        power_heuristic(last_pdf, scene.environment().pdf(ray.get_direction()))
+                    } else {
+                        1.0
+                    };
+                    let c = &throughput * &background(scene, &ray);
+                    radiance = Color::add(&radiance, &Color::scale(&c, weight));
+                    break;
+                }
+            };
+
+            let material = scene.material(hit.material());
+            if material.alpha(&hit) < crate::random::random() {
+                ray = pass_through(&ray, &hit);
+                continue;
+            }
+
+            if depth == 0 && material.is_holdout() {
+                return (radiance, 0.0);
+            }
+
+            if material.is_emissive() {
+                // Light reached through specular bounces from a diffuse vertex is in the photon map
+                let weight = if !after_diffuse {
+                    1.0
+                } else if specular_since {
+                    0.0
+                } else {
+                    power_heuristic(last_pdf, scene.light_pdf(&ray, &hit, f))
+                };
+                let c = &throughput * &to_ray_spectrum(&ray, material.emitted(&hit));
+                radiance = Color::add(&radiance, &Color::scale(&c, weight));
+            }
+
+            let mut scatter = match material.scatter(&ray, &hit) {
+                Some(s) => s,
+                None => break
+            };
+
+            let wo = -ray.get_direction().normalize();
+            let wi = scatter.ray.get_direction().normalize();
+            match material.eval(&wo, &wi, &hit) {
+                Some((_, pdf)) => {
+                    let mut direct = sample_lights(scene, &ray, &hit, material, f);
+                    if let Some(m) = map {
+                        direct = Color::add(&direct, &self.caustics(m, &ray, &hit, material));
+                    }
+                    radiance = Color::add(&radiance, &(&throughput * &direct));
+                    after_diffuse = true;
+                    specular_since = false;
+                    last_pdf = pdf;
+                },
+                None => {
+                    specular_since = true;
+                }
+            }
+
+            throughput = &throughput * &to_ray_spectrum(&ray, scatter.attenuation);
+            depth += 1;
+            if depth >= PhotonMapper::ROULETTE_DEPTH {
+                let q = throughput.r.max(throughput.g).max(throughput.b).min(0.95);
+                if q <= 0.0 || crate::random::random() >= q {
+                    break;
+                }
+                throughput = Color::scale(&throughput, 1.0 / q);
+            }
+
+            scatter.ray.set_wavelength(ray.get_wavelength());
+            ray = scatter.ray;
+        }
+
+        return (radiance, 1.0);
+    }
+}
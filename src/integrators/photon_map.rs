@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use crate::{color::Color, math::vector3::Vector3};
+
+/** Light particle stored where it landed on a surface */
+pub struct Photon {
+    pub p: Vector3,
+    /** Direction the photon came from, pointing away from the surface */
+    pub wi: Vector3,
+    pub n: Vector3,
+    pub flux: Color
+}
+
+/** Photons in a uniform hash grid with cells as large as the lookup radius,
+ * so a lookup only has to visit the 27 cells around the query point. */
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    cells: HashMap<(i32, i32, i32), Vec<usize>>,
+    radius: f32
+}
+
+impl PhotonMap {
+    pub fn new(photons: Vec<Photon>, radius: f32) -> Self {
+        let mut cells: HashMap<(i32, i32, i32), Vec<usize>> = HashMap::new();
+        for (i, photon) in photons.iter().enumerate() {
+            cells.entry(PhotonMap::cell(&photon.p, radius)).or_default().push(i);
+        }
+
+        PhotonMap {
+            photons, cells, radius
+        }
+    }
+
+    fn cell(p: &Vector3, size: f32) -> (i32, i32, i32) {
+        ((p.x / size).floor() as i32, (p.y / size).floor() as i32, (p.z / size).floor() as i32)
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /** Calls `f` for every photon within the lookup radius of p */
+    pub fn for_each_near<F: FnMut(&Photon)>(&self, p: &Vector3, mut f: F) {
+        let (cx, cy, cz) = PhotonMap::cell(p, self.radius);
+        let r2 = self.radius * self.radius;
+
+        for x in cx.saturating_sub(1)..=cx.saturating_add(1) {
+            for y in cy.saturating_sub(1)..=cy.saturating_add(1) {
+                for z in cz.saturating_sub(1)..=cz.saturating_add(1) {
+                    let indices = match self.cells.get(&(x, y, z)) {
+                        Some(i) => i,
+                        None => continue
+                    };
+                    for i in indices {
+                        let photon = &self.photons[*i];
+                        let d = &photon.p - p;
+                        if &d * &d <= r2 {
+                            f(photon);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static SIGNALS: AtomicU64 = AtomicU64::new(0);
+
+/** Catches SIGINT and SIGTERM so the render can stop at the end of the current pass and keep
+ * what it has. A second signal quits right away. */
+pub fn install() -> Result<(), String> {
+    let result = ctrlc::set_handler(|| {
+        if SIGNALS.fetch_add(1, Ordering::SeqCst) > 0 {
+            eprintln!("Interrupted again, quitting without saving.");
+            std::process::exit(130);
+        }
+        println!("Interrupted, stopping after the current pass. Interrupt again to quit right away.");
+    });
+
+    return match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Error installing signal handler: {}", e))
+    };
+}
+
+/** Whether the render was asked to stop */
+pub fn requested() -> bool {
+    return SIGNALS.load(Ordering::SeqCst) > 0;
+}
@@ -1,13 +1,23 @@
-use std::{env::args, time::Instant, str::FromStr, fs, sync::{Arc, RwLock}};
-
-use animation::animation::{AnimationChannel, AnimationKey, Interpolation};
-use crossbeam::{thread, channel::unbounded};
-use math::vector2::Vector2;
-use media::{ppm, media_info::PPMInfo};
-use structures::{scene::{Scene}, node::Node, materials::{diffuse::Diffuse, metal::Metal, dielectric::Dielectric}, material::Material};
+use std::{env::args, time::Instant, str::FromStr, fs, sync::{Mutex, atomic::{AtomicUsize, Ordering}}};
+
+use crossbeam::thread;
+use media::{ppm, png, pfm, media_info::{PPMInfo, PNGInfo, PFMInfo, ImageFormat}};
+use integrators::{integrator::IntegratorType, path::BounceLimits};
+use aov::Aov;
+use cryptomatte::{CryptomatteType, CryptomatteLayer};
+use denoiser::Features;
+use film::FilmTile;
+use checkpoint::{RenderState, PixelState};
+use filter::Filter;
+use tiles::{Tile, TileOrder};
+use samplers::sampler::{self, SamplerType};
+use progress::Progress;
+use stats::Phase;
+use scenes::{SceneType, Sky};
+use structures::scene::Scene;
 use utils::{GeneralInfo, RenderInfo};
 
-use crate::{color::Color, math::vector3::Vector3, ray::Ray, structures::{sphere::Sphere, camera::Camera}};
+use crate::{color::Color, math::vector3::Vector3, structures::camera::Camera};
 
 mod math;
 mod utils;
@@ -16,17 +26,115 @@ mod ray;
 mod structures;
 mod media;
 mod animation;
+mod spectrum;
+mod random;
+mod samplers;
+mod aov;
+mod cryptomatte;
+mod denoiser;
+mod adaptive;
+mod filter;
+mod film;
+mod tiles;
+mod checkpoint;
+mod interrupt;
+mod progress;
+mod stats;
+mod scenes;
+mod integrators;
+
+/* Frames rendered with -a */
+const FIRST_FRAME: u64 = 1;
+const LAST_FRAME: u64 = 23;
+
+/* Pixel coordinate of a region corner, given in pixels or, with a decimal point, as a fraction of the size */
+fn region_coordinate(value: &str, size: u64) -> Result<u64, String> {
+    let coordinate = if value.contains('.') {
+        match f32::from_str(value) {
+            Ok(f) if (0.0..=1.0).contains(&f) => (f * size as f32).round() as u64,
+            _ => {
+                return Err("Input error: Region invalid.".to_string());
+            }
+        }
+    } else {
+        match u64::from_str(value) {
+            Ok(c) => c,
+            Err(_) => {
+                return Err("Input error: Region invalid.".to_string());
+            }
+        }
+    };
 
-fn get_info_from_args() -> Result<GeneralInfo, String> {
-    let arguments: Vec<String> = args().collect();
+    if coordinate > size {
+        return Err("Input error: Region outside of the image.".to_string());
+    }
+    return Ok(coordinate);
+}
+
+/* Sun elevation, azimuth and turbidity following the argument at `i` */
+fn sky_arguments(arguments: &[String], i: usize) -> Result<Sky, String> {
+    if i + 3 >= arguments.len() {
+        return Err("Input error: Sky promised, but not specified.".to_string());
+    }
+
+    let mut values = [0.0; 3];
+    for (value, argument) in values.iter_mut().zip(&arguments[i+1..i+4]) {
+        *value = match f32::from_str(argument) {
+            Ok(v) => v,
+            Err(_) => {
+                return Err("Input error: Sky invalid.".to_string());
+            }
+        };
+    }
+
+    if !(-90.0..=90.0).contains(&values[0]) {
+        return Err("Input error: Sun elevation must be between -90 and 90 degrees.".to_string());
+    }
+    if !(1.7..=10.0).contains(&values[2]) {
+        return Err("Input error: Turbidity must be between 1.7 and 10.".to_string());
+    }
+    return Ok(Sky { elevation: values[0], azimuth: values[1], turbidity: values[2] });
+}
 
+/* Settings from the command line arguments, the first of which is the program */
+fn get_info_from_args(arguments: &[String]) -> Result<GeneralInfo, String> {
     let mut output_filename = "out".to_string();
     let mut output_width = 400;
     let mut output_height = 200;
     let mut aa_sampling = 50;
+    let mut samples_given = false;
     let mut ray_recursion_depth = 50;
     let mut threads = 10;
     let mut animation = false;
+    let mut spectral = false;
+    let mut out_format = ImageFormat::Ppm;
+    let mut transparent = false;
+    let mut integrator = IntegratorType::Path;
+    let mut bounces = BounceLimits::unlimited();
+    let mut clamp = 0.0;
+    let mut photons = 100000;
+    let mut photon_radius = 0.05;
+    let mut photon_passes = 0;
+    let mut aovs = Vec::new();
+    let mut cryptomatte = Vec::new();
+    let mut cryptomatte_ranks = 6;
+    let mut denoise = false;
+    let mut sampler = SamplerType::Independent;
+    let mut seed = 0;
+    let mut adaptive_threshold = 0.0;
+    let mut min_samples = 16;
+    let mut filter = Filter::Box;
+    let mut tile_size = 32;
+    let mut tile_order = TileOrder::Spiral;
+    let mut pass_samples = 0;
+    let mut resume = false;
+    let mut time_limit = 0.0;
+    let mut region_args: Option<&[String]> = None;
+    let mut crop = false;
+    let mut scene = SceneType::Spheres;
+    let mut sky = None;
+    let mut sky_end = None;
+    let mut voxels = None;
 
     for i in 0..arguments.len() {
         if arguments[i] == "-o" {
@@ -65,7 +173,8 @@ fn get_info_from_args() -> Result<GeneralInfo, String> {
                     Err(_) => {
                         return Err("Input error: Sampling count not valid.".to_string());
                     }
-                }
+                };
+                samples_given = true;
             }
         }
 
@@ -87,8 +196,8 @@ fn get_info_from_args() -> Result<GeneralInfo, String> {
                 return Err("Input error: Threads number promised, but not specified.".to_string());
             } else {
                 threads = match u64::from_str(arguments[i+1].as_str()) {
-                    Ok(r) => r,
-                    Err(_) => {
+                    Ok(r) if r > 0 => r,
+                    _ => {
                         return Err("Input error: Threads number invalid.".to_string());
                     }
                 }
@@ -98,6 +207,389 @@ fn get_info_from_args() -> Result<GeneralInfo, String> {
         else if arguments[i] == "-a" {
             animation = true;
         }
+
+        else if arguments[i] == "--spectral" {
+            spectral = true;
+        }
+
+        else if arguments[i] == "-f" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Output format promised, but not specified.".to_string());
+            } else {
+                out_format = match arguments[i+1].as_str() {
+                    "ppm" => ImageFormat::Ppm,
+                    "png" => ImageFormat::Png,
+                    _ => {
+                        return Err("Input error: Output format invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--transparent" {
+            transparent = true;
+        }
+
+        else if arguments[i] == "--integrator" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Integrator promised, but not specified.".to_string());
+            } else {
+                integrator = match IntegratorType::from_name(arguments[i+1].as_str()) {
+                    Some(t) => t,
+                    None => {
+                        return Err("Input error: Integrator invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--scene" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Scene promised, but not specified.".to_string());
+            } else {
+                scene = match SceneType::from_name(arguments[i+1].as_str()) {
+                    Some(s) => s,
+                    None => {
+                        return Err("Input error: Scene invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--sky" {
+            sky = Some(sky_arguments(arguments, i)?);
+        }
+
+        else if arguments[i] == "--sky-end" {
+            sky_end = Some(sky_arguments(arguments, i)?);
+        }
+
+        else if arguments[i] == "--voxels" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Voxel file promised, but not specified.".to_string());
+            } else {
+                voxels = Some(arguments[i+1].clone());
+            }
+        }
+
+        else if arguments[i] == "--denoise" {
+            denoise = true;
+        }
+
+        else if arguments[i] == "--pass-samples" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Samples per pass promised, but not specified.".to_string());
+            } else {
+                pass_samples = match u64::from_str(arguments[i+1].as_str()) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return Err("Input error: Samples per pass invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--resume" {
+            resume = true;
+        }
+
+        else if arguments[i] == "--region" {
+            if i + 4 >= arguments.len() {
+                return Err("Input error: Region promised, but not specified.".to_string());
+            } else {
+                region_args = Some(&arguments[i+1..i+5]);
+            }
+        }
+
+        else if arguments[i] == "--crop" {
+            crop = true;
+        }
+
+        else if arguments[i] == "--time-limit" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Time limit promised, but not specified.".to_string());
+            } else {
+                time_limit = match f32::from_str(arguments[i+1].as_str()) {
+                    Ok(t) if t >= 0.0 => t,
+                    _ => {
+                        return Err("Input error: Time limit invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--tile-size" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Tile size promised, but not specified.".to_string());
+            } else {
+                tile_size = match u64::from_str(arguments[i+1].as_str()) {
+                    Ok(t) if t > 0 => t,
+                    _ => {
+                        return Err("Input error: Tile size invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--tile-order" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Tile order promised, but not specified.".to_string());
+            } else {
+                tile_order = match TileOrder::from_name(arguments[i+1].as_str()) {
+                    Some(o) => o,
+                    None => {
+                        return Err("Input error: Tile order invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--filter" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Filter promised, but not specified.".to_string());
+            } else {
+                filter = match Filter::from_name(arguments[i+1].as_str()) {
+                    Some(f) => f,
+                    None => {
+                        return Err("Input error: Filter invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--adaptive" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Adaptive sampling threshold promised, but not specified.".to_string());
+            } else {
+                adaptive_threshold = match f32::from_str(arguments[i+1].as_str()) {
+                    Ok(t) => t,
+                    Err(_) => {
+                        return Err("Input error: Adaptive sampling threshold invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--min-samples" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Minimum sampling count promised, but not specified.".to_string());
+            } else {
+                min_samples = match u64::from_str(arguments[i+1].as_str()) {
+                    Ok(m) => m,
+                    Err(_) => {
+                        return Err("Input error: Minimum sampling count invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--seed" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Seed promised, but not specified.".to_string());
+            } else {
+                seed = match u64::from_str(arguments[i+1].as_str()) {
+                    Ok(s) => s,
+                    Err(_) => {
+                        return Err("Input error: Seed invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--sampler" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Sampler promised, but not specified.".to_string());
+            } else {
+                sampler = match SamplerType::from_name(arguments[i+1].as_str()) {
+                    Some(t) => t,
+                    None => {
+                        return Err("Input error: Sampler invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--aov" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Render passes promised, but not specified.".to_string());
+            } else {
+                for name in arguments[i+1].split(',') {
+                    match Aov::from_name(name) {
+                        Some(a) => {
+                            if !aovs.contains(&a) {
+                                aovs.push(a);
+                            }
+                        },
+                        None => {
+                            return Err(format!("Input error: Render pass '{}' invalid.", name));
+                        }
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--cryptomatte" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Cryptomatte layers promised, but not specified.".to_string());
+            } else {
+                for name in arguments[i+1].split(',') {
+                    match CryptomatteType::from_name(name) {
+                        Some(t) => {
+                            if !cryptomatte.contains(&t) {
+                                cryptomatte.push(t);
+                            }
+                        },
+                        None => {
+                            return Err(format!("Input error: Cryptomatte layer '{}' invalid.", name));
+                        }
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--cryptomatte-ranks" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Cryptomatte rank count promised, but not specified.".to_string());
+            } else {
+                cryptomatte_ranks = match u64::from_str(arguments[i+1].as_str()) {
+                    Ok(r) => r,
+                    Err(_) => {
+                        return Err("Input error: Cryptomatte rank count invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--diffuse-bounces" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Diffuse bounce limit promised, but not specified.".to_string());
+            } else {
+                bounces.diffuse = match u64::from_str(arguments[i+1].as_str()) {
+                    Ok(b) => b,
+                    Err(_) => {
+                        return Err("Input error: Diffuse bounce limit invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--glossy-bounces" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Glossy bounce limit promised, but not specified.".to_string());
+            } else {
+                bounces.glossy = match u64::from_str(arguments[i+1].as_str()) {
+                    Ok(b) => b,
+                    Err(_) => {
+                        return Err("Input error: Glossy bounce limit invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--transmission-bounces" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Transmission bounce limit promised, but not specified.".to_string());
+            } else {
+                bounces.transmission = match u64::from_str(arguments[i+1].as_str()) {
+                    Ok(b) => b,
+                    Err(_) => {
+                        return Err("Input error: Transmission bounce limit invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--volume-bounces" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Volume bounce limit promised, but not specified.".to_string());
+            } else {
+                bounces.volume = match u64::from_str(arguments[i+1].as_str()) {
+                    Ok(b) => b,
+                    Err(_) => {
+                        return Err("Input error: Volume bounce limit invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--clamp" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Clamp value promised, but not specified.".to_string());
+            } else {
+                clamp = match f32::from_str(arguments[i+1].as_str()) {
+                    Ok(c) => c,
+                    Err(_) => {
+                        return Err("Input error: Clamp value invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--photons" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Photon count promised, but not specified.".to_string());
+            } else {
+                photons = match u64::from_str(arguments[i+1].as_str()) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return Err("Input error: Photon count invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--photon-radius" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Photon radius promised, but not specified.".to_string());
+            } else {
+                photon_radius = match f32::from_str(arguments[i+1].as_str()) {
+                    Ok(r) if r > 0.0 && r.is_finite() => r,
+                    _ => {
+                        return Err("Input error: Photon radius invalid.".to_string());
+                    }
+                }
+            }
+        }
+
+        else if arguments[i] == "--photon-passes" {
+            if i + 1 >= arguments.len() {
+                return Err("Input error: Photon pass count promised, but not specified.".to_string());
+            } else {
+                photon_passes = match u64::from_str(arguments[i+1].as_str()) {
+                    Ok(p) if p > 0 => p,
+                    _ => {
+                        return Err("Input error: Photon pass count invalid.".to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    // Normalized coordinates need the resolution, which may come after the region
+    let region = match region_args {
+        Some(args) => {
+            let x_start = region_coordinate(&args[0], output_width)?;
+            let y_start = region_coordinate(&args[1], output_height)?;
+            let x_end = region_coordinate(&args[2], output_width)?;
+            let y_end = region_coordinate(&args[3], output_height)?;
+            if x_start >= x_end || y_start >= y_end {
+                return Err("Input error: Region is empty.".to_string());
+            }
+            Some(Tile { x_start, x_end, y_start, y_end })
+        },
+        None => None
+    };
+
+    if voxels.is_some() && scene != SceneType::Volumes {
+        return Err("Input error: Voxel files can only be rendered in the volumes scene.".to_string());
+    }
+
+    if sky_end.is_some() && sky.is_none() {
+        return Err("Input error: End of the sky animation given without the sky.".to_string());
+    }
+
+    if time_limit > 0.0 && !integrator.renders_in_passes() {
+        return Err(format!("Input error: The {} integrator can't render with a time limit.", integrator.name()));
     }
 
     return Ok(GeneralInfo {
@@ -105,42 +597,42 @@ fn get_info_from_args() -> Result<GeneralInfo, String> {
         out_width: output_width,
         out_height: output_height,
         aa_sampling: aa_sampling,
+        unlimited_samples: time_limit > 0.0 && !samples_given,
         ray_recursion: ray_recursion_depth,
         threads: threads,
-        animation: animation
+        animation: animation,
+        spectral: spectral,
+        out_format: out_format,
+        transparent: transparent,
+        integrator: integrator,
+        bounces: bounces,
+        clamp: clamp,
+        photons: photons,
+        photon_radius: photon_radius,
+        photon_passes: photon_passes,
+        aovs: aovs,
+        cryptomatte: cryptomatte,
+        cryptomatte_ranks: cryptomatte_ranks,
+        denoise: denoise,
+        sampler: sampler,
+        seed: seed,
+        adaptive_threshold: adaptive_threshold,
+        min_samples: min_samples,
+        filter: filter,
+        tile_size: tile_size,
+        tile_order: tile_order,
+        pass_samples: pass_samples,
+        resume: resume,
+        time_limit: time_limit,
+        region: region,
+        crop: crop,
+        scene: scene,
+        sky: sky,
+        sky_end: sky_end,
+        voxels: voxels
     });
 }
 
-fn ray_color(scene: &Scene, ray: Ray, depth: u64, f: f32) -> Color {
-    if depth <= 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
-
-    let trace_res = scene.trace(&ray, 0.0001, 10000.0, f);
-
-    if trace_res.is_some() {
-        let hit = trace_res.unwrap();
-        
-        let scatter_opt = hit.material().scatter(&ray, &hit);
-        if scatter_opt.is_some() {
-            let scatter = scatter_opt.unwrap();
-            return &scatter.attenuation * &ray_color(scene, scatter.ray, depth - 1, f);
-        }
-        return Color::new(0.0, 0.0, 0.0);
-    }
-
-    let d = ray.get_direction().normalize();
-    let t = 0.5 * (d.y + 1.0);
-    
-    let mut c1 = Color::new(1.0, 1.0, 1.0);
-    let mut c2 = Color::new(0.5, 0.7, 1.0);
-
-    c1 = c1 * (1.0 - t);
-    c2 = c2 * t;
-
-    return Color::add(&c1, &c2);
-}
-
 /*fn test_scene() -> Scene {
     let mut scene = Scene::new();
 
@@ -268,78 +760,9 @@ fn ray_color(scene: &Scene, ray: Ray, depth: u64, f: f32) -> Color {
     return scene;
 }*/
 
-fn test_scene2() -> Scene {
-    let mut scene = Scene::new();
-
-    let material1 = Diffuse::new(Color::new(0.8, 0.8, 0.0));
-    let material2 = Diffuse::new(Color::new(0.1, 0.2, 0.5));
-    let material3 = Diffuse::new(Color::new(0.8, 0.8, 0.0));
-    let material4 = Diffuse::new(Color::new(0.8, 0.8, 0.0));
-
-    let mut node1 = Node::new();
-    let sphere1 = Sphere::new(
-        Vector3::new(0.0, 0.0, -1.0), 0.5,
-        Box::new(material1)
-    );
-    let mut node2 = Node::new();
-    let sphere2 = Sphere::new(
-        Vector3::new(0.0, -100.5, -1.0), 100.0,
-        Box::new(material2)
-    );
-    let mut node3 = Node::new();
-    let sphere3 = Sphere::new(
-        Vector3::new(-1.0, 0.0, -1.0), 0.5,
-        Box::new(material3)
-    );
-    let mut node4 = Node::new();
-    let sphere4 = Sphere::new(
-        Vector3::new(1.0, 0.0, -1.0), 0.5,
-        Box::new(material4)
-    );
-
-    node1.set_renderable(Box::new(sphere1));
-    node2.set_renderable(Box::new(sphere2));
-    node3.set_renderable(Box::new(sphere3));
-    node4.set_renderable(Box::new(sphere4));
-
-    let mut animation_pos_y = AnimationChannel::new();
-    let mut anim_key1 = AnimationKey::new(1.0, 0.0);
-    let mut anim_key2 = AnimationKey::new(12.0, 1.0);
-    let mut anim_key3 = AnimationKey::new(23.0, 0.0);
-    anim_key1.change_interpolation(Interpolation::Bezier(
-        vec![Vector2::new(0.0, 0.0), Vector2::new(3.0, 0.75)]
-    ));
-    anim_key3.change_interpolation(Interpolation::Bezier(
-        vec![Vector2::new(21.0, 0.75), Vector2::new(23.0, 0.0)]
-    ));
-    anim_key2.change_interpolation(Interpolation::Bezier(
-        vec![Vector2::new(6.0, 1.0), Vector2::new(18.0, 1.0)]
-    ));
-    animation_pos_y.add_key(anim_key1);
-    animation_pos_y.add_key(anim_key2);
-    animation_pos_y.add_key(anim_key3);
-
-    node3.set_animation_channel("translation_y".to_string(), animation_pos_y);
-
-    let node1_arc = Arc::new(RwLock::new(node1));
-    let node2_arc = Arc::new(RwLock::new(node2));
-    let node3_arc = Arc::new(RwLock::new(node3));
-    let node4_arc = Arc::new(RwLock::new(node4));
-
-    /*node3.add_child(node4);
-    scene.add_child(node1);
-    scene.add_child(node2);
-    scene.add_child(node3);*/
-
-    Node::add_child(node3_arc.clone(), node4_arc);
-    scene.add_child(node1_arc);
-    scene.add_child(node2_arc);
-    scene.add_child(node3_arc);
-
-    return scene;
-}
-
-fn render(render_info: RenderInfo) -> Vec<Color> {
+/* Renders samples `sample_start` to `sample_end` of a part of the image onto the film tile,
+ * gathering the rest in the states of its pixels. Returns the number of samples taken. */
+fn render(render_info: RenderInfo, tile: &mut FilmTile, pixels: &mut [PixelState]) -> u64 {
     let hs = render_info.height_start;
     let he = render_info.height_end;
     let ws = render_info.width_start;
@@ -353,94 +776,472 @@ fn render(render_info: RenderInfo) -> Vec<Color> {
         }
     };
     let frame = render_info.frame as f32;
-
-    let mut data: Vec<Color> = Vec::new();
+    let integrator = render_info.integrator;
+    let aovs = render_info.aovs;
+    let cryptomatte = render_info.cryptomatte;
+    let spectral = info.spectral && integrator.supports_spectral();
+    let adaptive = info.adaptive_threshold > 0.0 && integrator.supports_adaptive();
+    let mut total_samples = 0;
+
+    random::set_sampler(Some(info.sampler.create(info.aa_sampling, sampler::frame_seed(info.seed, render_info.frame))));
     for h in hs..he {
         for w in ws..we {
-            let mut c = Color::new(0.0, 0.0, 0.0);
-            for _ in 0..info.aa_sampling {
-                let ray = camera.get_ray(w, h, info.out_width, info.out_height);
-                let ray_color = ray_color(&scene, ray, info.ray_recursion, frame);
-                c = c + ray_color;
-            }
+            let pixel = &mut pixels[((h - hs) * (we - ws) + w - ws) as usize];
+            for sample in render_info.sample_start..render_info.sample_end {
+                if adaptive && pixel.stats.converged(info.min_samples, info.adaptive_threshold) {
+                    break;
+                }
 
-            if info.aa_sampling > 0 {
-                c = c * (1.0 / info.aa_sampling as f32);
+                random::start_pixel_sample(w, h, sample);
+                stats::add_camera_sample();
+                let x = w as f32 + random::random();
+                let y = h as f32 + random::random();
+                let mut ray = camera.get_ray_at(x, y, info.out_width, info.out_height);
+                let aov_ray = ray.copy();
+                let (direct, indirect, ray_alpha) = if spectral {
+                    let wavelength = spectrum::sample_wavelength();
+                    ray.set_wavelength(Some(wavelength));
+                    let (direct, indirect, value_alpha) = integrator.camera_ray_light(scene, ray, frame);
+                    (spectrum::spectral_to_rgb(direct.r, wavelength), spectrum::spectral_to_rgb(indirect.r, wavelength), value_alpha)
+                } else {
+                    integrator.camera_ray_light(scene, ray, frame)
+                };
+
+                if !aovs.is_empty() || !cryptomatte.is_empty() {
+                    let hit = scene.trace(&aov_ray, 0.0001, 10000.0, frame);
+                    for (aov, value) in aovs.iter().zip(pixel.aovs.iter_mut()) {
+                        if aov.is_averaged() || sample == 0 {
+                            let v = aov.value(scene, &aov_ray, hit.as_ref(), &direct, &indirect);
+                            *value = Color::add(value, &v);
+                        }
+                    }
+                    if let Some(hit) = hit.as_ref() {
+                        for (layer, pixel_coverage) in cryptomatte.iter().zip(pixel.coverage.iter_mut()) {
+                            if let Some(id) = layer.id(hit) {
+                                cryptomatte::add_coverage(pixel_coverage, id, 1.0);
+                            }
+                        }
+                    }
+                }
+
+                let c = Color::add(&direct, &indirect);
+                pixel.stats.add(c.luminance());
+                tile.add_sample(x, y, &c, ray_alpha);
+                pixel.samples += 1;
+                total_samples += 1;
             }
-            c.clamp();
-            data.push(c);
         }
     }
-    return data;
+    random::set_sampler(None);
+    return total_samples;
+}
+
+/* Part of the image being rendered */
+fn render_region(info: &GeneralInfo) -> Tile {
+    return match info.region {
+        Some(r) => r,
+        None => Tile { x_start: 0, x_end: info.out_width, y_start: 0, y_end: info.out_height }
+    };
+}
+
+/* Settings that must match for a checkpoint to be resumed, i.e. everything but the sample
+ * count that changes what a sample adds to the image */
+fn checkpoint_signature(info: &GeneralInfo, frame: u64, aovs: &[Aov], cryptomatte: &[CryptomatteLayer]) -> String {
+    let aov_names: Vec<&str> = aovs.iter().map(|a| a.name()).collect();
+    let layer_names: Vec<&str> = cryptomatte.iter().map(|l| l.kind().name()).collect();
+    let region = render_region(info);
+    let bounces = &info.bounces;
+    /* The photon maps only matter to the photon integrator, whose default count follows -s */
+    let photon_passes = match info.integrator {
+        IntegratorType::PhotonMapping => info.photon_passes(),
+        _ => 0
+    };
+    return format!("scene {} sky {:?} {:?} voxels {:?} {}x{} region {} {} {} {} frame {} seed {} sampler {} filter {} \
+        integrator {} spectral {} transparent {} depth {} bounces {} {} {} {} clamp {} photons {} {} {} \
+        adaptive {} {} aovs {} cryptomatte {}",
+        info.scene.name(), info.sky, info.sky_end, info.voxels, info.out_width, info.out_height,
+        region.x_start, region.y_start, region.x_end, region.y_end, frame, info.seed, info.sampler.name(), info.filter.name(),
+        info.integrator.name(), info.spectral, info.transparent, info.ray_recursion,
+        bounces.diffuse, bounces.glossy, bounces.transmission, bounces.volume, info.clamp,
+        info.photons, info.photon_radius, photon_passes, info.adaptive_threshold, info.min_samples,
+        aov_names.join(","), layer_names.join(","));
+}
+
+/* Samples per pixel taken so far, out of the limit if there is one */
+fn samples_taken(samples: u64, limit: u64) -> String {
+    if limit == u64::MAX {
+        return samples.to_string();
+    }
+    return format!("{} of {}", samples, limit);
 }
 
-fn render_still(info: &GeneralInfo, scene: &mut Scene, frame: u64) -> Result<(), String> {
+/* Samples per pixel of the passes of a time-limited render without --pass-samples */
+const TIME_LIMIT_PASS_SAMPLES: u64 = 4;
+
+fn render_still(info: &GeneralInfo, scene: &mut Scene, frame: u64, progress: &mut Progress) -> Result<(), String> {
     let height = info.out_height;
     let width = info.out_width;
-    let mut data: Vec<Color> = Vec::new();
-    for _ in 0..height*width {
-        data.push(Color::new(0.0, 0.0, 0.0));
-    }
+    let setup_time = Instant::now();
 
     scene.update_transforms(frame as f32);
 
     let scene = &scene;
+    let cryptomatte: Vec<CryptomatteLayer> = info.cryptomatte.iter().map(|t| CryptomatteLayer::new(*t, scene)).collect();
+    let cryptomatte = cryptomatte.as_slice();
+
+    // The denoiser needs feature passes on top of the requested ones
+    let mut aovs = info.aovs.clone();
+    if info.denoise {
+        for feature in [Aov::Albedo, Aov::Normal, Aov::Depth, Aov::Variance] {
+            if !aovs.contains(&feature) {
+                aovs.push(feature);
+            }
+        }
+    }
+    let aovs = aovs.as_slice();
+    let mut integrator = info.integrator.create(info);
+    integrator.prepare(scene, frame as f32);
+    let integrator = integrator.as_ref();
+
+    // Integrators that don't estimate pixels by their samples alone have to render in one go.
+    // A time limit needs passes to stop between, so it picks a pass size if none was given.
+    let progressive = (info.pass_samples > 0 || info.time_limit > 0.0) && integrator.supports_adaptive();
+    let pass_samples = if !progressive {
+        info.aa_sampling.max(1)
+    } else if info.pass_samples > 0 {
+        info.pass_samples
+    } else {
+        TIME_LIMIT_PASS_SAMPLES
+    };
+    // A time limit without -s leaves the passes going until the time is up
+    let sample_limit = if progressive && info.unlimited_samples { u64::MAX } else { info.aa_sampling };
+    let checkpoint = format!("{}.checkpoint", info.out_filename);
+    let signature = checkpoint_signature(info, frame, aovs, cryptomatte);
+
+    let mut state = RenderState::new(width, height, info.filter, aovs.len(), cryptomatte.len());
+    if info.resume && progressive {
+        if state.load(&checkpoint, &signature)? {
+            progress.message(&format!("Resuming from {} samples per pixel.", state.samples));
+        } else {
+            progress.message("No checkpoint found, starting from scratch.");
+        }
+    }
+    stats::add_time(Phase::Setup, setup_time.elapsed());
+
+    // Part of the frame done after the given tiles of a pass. A time limit usually ends the
+    // frame long before the samples are all taken, so the time taken counts if it's further.
+    let start_time = Instant::now();
+    let aa_sampling = sample_limit.max(1);
+    let frame_done = |sample_start: u64, sample_end: u64, tiles_done: f32| {
+        let samples = if progressive {
+            (sample_start as f32 + (sample_end - sample_start) as f32 * tiles_done) / aa_sampling as f32
+        } else {
+            tiles_done
+        };
+        if info.time_limit > 0.0 {
+            return samples.max(start_time.elapsed().as_secs_f32() / info.time_limit);
+        }
+        return samples;
+    };
+    progress.start_frame(if progressive { state.samples as f32 / aa_sampling as f32 } else { 0.0 });
 
-    let (st, rt) = unbounded();
-    let mut receivers = Vec::new();
+    // Integrators splatting the whole picture only run their render threads
+    let region = render_region(info);
+    let tiles = if integrator.samples_pixels() || !aovs.is_empty() || !cryptomatte.is_empty() {
+        tiles::tiles(&region, info.tile_size, info.tile_order)
+    } else {
+        Vec::new()
+    };
+    let mut first_pass = true;
+    let mut done = 0.0;
+    while first_pass || state.samples < sample_limit {
+        let pass_start_time = Instant::now();
+        let sample_start = state.samples;
+        let sample_end = sample_start.saturating_add(pass_samples).min(sample_limit).max(sample_start);
+
+        // Threads pull tiles off the queue until it's empty and add them to the image when done
+        let next_tile = AtomicUsize::new(0);
+        let tiles_done = AtomicUsize::new(0);
+        let progress_ref = &*progress;
+        let film = Mutex::new(&mut state.film);
+        let pixels = Mutex::new(&mut state.pixels);
+
+        thread::scope(|s| {
+            for i in 0..info.threads {
+                let tiles = &tiles;
+                let next_tile = &next_tile;
+                let tiles_done = &tiles_done;
+                let frame_done = &frame_done;
+                let film = &film;
+                let pixels = &pixels;
+
+                s.spawn(move |_| {
+                    integrator.render_thread(scene, i, info.threads, frame as f32);
+
+                    while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                        // Without passes to stop between, the tiles done so far are kept instead
+                        if !progressive && interrupt::requested() {
+                            break;
+                        }
+                        let mut film_tile = film.lock().unwrap().tile(tile.x_start, tile.x_end, tile.y_start, tile.y_end);
+                        let mut tile_pixels = Vec::new();
+                        {
+                            let mut pixels = pixels.lock().unwrap();
+                            for y in tile.y_start..tile.y_end {
+                                for x in tile.x_start..tile.x_end {
+                                    tile_pixels.push(std::mem::take(&mut pixels[(y * width + x) as usize]));
+                                }
+                            }
+                        }
+
+                        let render_info = RenderInfo {
+                            width_start: tile.x_start,
+                            width_end: tile.x_end,
+                            height_start: tile.y_start,
+                            height_end: tile.y_end,
+                            sample_start,
+                            sample_end,
+                            info,
+                            scene,
+                            frame,
+                            integrator,
+                            aovs,
+                            cryptomatte
+                        };
+                        render(render_info, &mut film_tile, &mut tile_pixels);
+
+                        film.lock().unwrap().merge(film_tile);
+                        let mut pixels = pixels.lock().unwrap();
+                        let mut tile_pixels = tile_pixels.into_iter();
+                        for y in tile.y_start..tile.y_end {
+                            for x in tile.x_start..tile.x_end {
+                                pixels[(y * width + x) as usize] = tile_pixels.next().unwrap();
+                            }
+                        }
+                        drop(pixels);
+
+                        let done = tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+                        progress_ref.update(frame_done(sample_start, sample_end, done as f32 / tiles.len() as f32), false);
+                    }
+                });
+            }
+        }).unwrap();
+        stats::add_time(Phase::Rendering, pass_start_time.elapsed());
+        let output_time = Instant::now();
+
+        state.samples = sample_end;
+        first_pass = false;
+        let tiles_done = if tiles.is_empty() { 1.0 } else { tiles_done.into_inner() as f32 / tiles.len() as f32 };
+        done = frame_done(sample_start, sample_end, tiles_done);
+
+        // Light images of this run are added to those of the runs before
+        let light = integrator.light_image().map(|image| {
+            if state.light.is_empty() {
+                return image;
+            }
+            image.iter().zip(state.light.iter()).map(|(a, b)| Color::add(a, b)).collect()
+        });
+
+        let light_paths = integrator.light_paths(state.total_samples());
+
+        if progressive {
+            progress.message(&format!("Finished pass at {} samples per pixel.", samples_taken(state.samples, sample_limit)));
+            state.save(&checkpoint, &signature, light.as_deref())?;
+        }
+        output_still(info, &state, light.as_deref().map(|l| (l, light_paths)), aovs, cryptomatte)?;
+        stats::add_time(Phase::Output, output_time.elapsed());
 
-    thread::scope(|s| {
-        for i in 0..info.threads {
-            let thread_share = height / info.threads;
-            let min_height = i*thread_share;
-            let max_height = if i < (info.threads - 1) {
-                min_height + thread_share
+        if interrupt::requested() {
+            if progressive {
+                progress.message(&format!("Stopped at {} samples per pixel.", samples_taken(state.samples, sample_limit)));
             } else {
-                height
-            };
-            
-            let min_width = 0;
-            let max_width = width;
-
-            let st_clone = st.clone();
-            let rcv_clone = rt.clone();
-            receivers.push(rcv_clone);
-
-            s.spawn(move |_| {
-                //println!("Rows {}-{} started", min_height, max_height);
-                let render_info = RenderInfo {
-                    width_start: min_width,
-                    width_end: max_width,
-                    height_start: min_height,
-                    height_end: max_height,
-                    info,
-                    scene,
-                    frame,
-                };
-                let data_part = render(render_info);
-                //println!("Rows {}-{} finished", min_height, max_height);
-                let msg = (min_height, max_height, data_part);
-                st_clone.send(msg).unwrap();
-            });
+                progress.message("Stopped before all tiles were rendered.");
+            }
+            break;
+        }
+        if state.samples >= sample_limit {
+            break;
+        }
+        // Stop if another pass like the last one would run over the budget
+        let elapsed = start_time.elapsed().as_secs_f32();
+        if info.time_limit > 0.0 && elapsed + pass_start_time.elapsed().as_secs_f32() > info.time_limit {
+            progress.message(&format!("Time limit reached at {} samples per pixel.", samples_taken(state.samples, sample_limit)));
+            break;
+        }
+    }
+    progress.finish_frame(done);
+
+    if info.adaptive_threshold > 0.0 {
+        progress.message(&format!("Adaptive sampling took {:.1} samples per pixel on average.",
+            state.total_samples() as f32 / ((region.x_end - region.x_start) * (region.y_end - region.y_start)).max(1) as f32));
+    }
+    return Ok(());
+}
+
+/* Pixels of an image inside the region, row by row */
+fn crop<T>(image: Vec<T>, width: u64, region: &Tile) -> Vec<T> {
+    return image.into_iter().enumerate().filter(|(i, _)| {
+        let (x, y) = (*i as u64 % width, *i as u64 / width);
+        x >= region.x_start && x < region.x_end && y >= region.y_start && y < region.y_end
+    }).map(|(_, p)| p).collect();
+}
+
+/* Image to write for the pixels of the rendered region. Without a region or when cropping,
+ * that's the pixels themselves. Otherwise they replace their part of the image written by an
+ * earlier render, loaded with `load`, or of an empty one if there is none yet. */
+fn region_output<T>(info: &GeneralInfo, part: Vec<T>, load: impl FnOnce() -> Result<(u64, u64, Vec<T>), std::io::Error>,
+        empty: impl Fn() -> T) -> Result<Vec<T>, String> {
+    let region = match info.region {
+        Some(r) if !info.crop => r,
+        _ => {
+            return Ok(part);
+        }
+    };
+
+    let mut image = match load() {
+        Ok((w, h, image)) => {
+            if w != info.out_width || h != info.out_height {
+                return Err(format!("Error compositing region: the existing image is {}x{}, not {}x{}.",
+                    w, h, info.out_width, info.out_height));
+            }
+            image
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            (0..info.out_width * info.out_height).map(|_| empty()).collect()
+        },
+        Err(e) => {
+            return Err(format!("Error compositing region: {}", e));
         }
+    };
+
+    let mut part = part.into_iter();
+    for y in region.y_start..region.y_end {
+        for x in region.x_start..region.x_end {
+            image[(y * info.out_width + x) as usize] = part.next().unwrap();
+        }
+    }
+    return Ok(image);
+}
+
+/* Writes the image and the render passes of the samples accumulated so far, adding the light
+ * image summed over the given number of paths */
+fn output_still(info: &GeneralInfo, state: &RenderState, light: Option<(&[Color], u64)>,
+        aovs: &[Aov], cryptomatte: &[CryptomatteLayer]) -> Result<(), String> {
+    let width = info.out_width;
+    let height = info.out_height;
+    let ranks = info.cryptomatte_ranks as usize;
+    let region = render_region(info);
+    let region_width = region.x_end - region.x_start;
+    let region_height = region.y_end - region.y_start;
+
+    let (mut data, mut alpha) = state.film.resolve();
+
+    // Light images are summed over all their paths, the average count per pixel turns them into radiance
+    if let Some((light_image, paths)) = light {
+        let scale = (width * height) as f32 / paths.max(1) as f32;
+        for i in 0..data.len() {
+            data[i] = Color::add(&data[i], &Color::scale(&light_image[i], scale));
+        }
+        // Without camera samples, the light image is the whole picture
+        if state.total_samples() == 0 {
+            alpha = vec![1.0; alpha.len()];
+        }
+    }
+
+    // Only the rendered region is denoised and written
+    let mut data = crop(data, width, &region);
+    let alpha = crop(alpha, width, &region);
+    let pixels = crop(state.pixels.iter().collect(), width, &region);
+
+    let passes: Vec<Vec<Color>> = aovs.iter().enumerate().map(|(a, aov)| {
+        pixels.iter().map(|p| aov.finish(&p.aovs[a], p.samples)).collect()
+    }).collect();
+
+    if info.denoise {
+        let pass = |aov: Aov| &passes[aovs.iter().position(|a| *a == aov).unwrap()];
+        let features = Features {
+            albedo: pass(Aov::Albedo),
+            normal: pass(Aov::Normal),
+            depth: pass(Aov::Depth),
+            variance: pass(Aov::Variance)
+        };
+        data = denoiser::denoise(&data, &features, region_width, region_height, info.threads);
+    }
+    for c in data.iter_mut() {
+        c.clamp();
+    }
 
-        for receiver in receivers {
-            let (h1, _, d) = receiver.recv().unwrap();
-            let start = (h1*width) as usize;
-            for i in 0..d.len() {
-                data[i+start] = d[i].copy();
+    // A region goes into the image of an earlier render unless it's cropped
+    let composite = info.region.is_some() && !info.crop;
+    let (out_width, out_height) = if composite { (width, height) } else { (region_width, region_height) };
+
+    for (aov, pass) in info.aovs.iter().zip(passes) {
+        let filename = format!("{}_{}", info.out_filename, aov.name());
+        let pass = region_output(info, pass, || pfm::decode(&filename), || Color::new(0.0, 0.0, 0.0))?;
+        let pfm_info = PFMInfo {
+            filename,
+            width: out_width,
+            height: out_height
+        };
+        if let Err(e) = pfm::encode(&pfm_info, &pass) {
+            return Err(format!("Error outputing {} pass: {}", aov.name(), e));
+        }
+    }
+
+    // Every rank holds an ID in the red and its coverage in the green channel
+    for (l, layer) in cryptomatte.iter().enumerate() {
+        let mut rank_images: Vec<Vec<Color>> = (0..ranks).map(|_| Vec::with_capacity(pixels.len())).collect();
+        for p in pixels.iter() {
+            let n = p.samples.max(1) as f32;
+            let coverage = p.coverage[l].iter().map(|(id, amount)| (*id, amount / n)).collect();
+            for (rank, (id, amount)) in cryptomatte::top_coverage(coverage, ranks).into_iter().enumerate() {
+                rank_images[rank].push(Color::new(id, amount, 0.0));
             }
         }
-    }).unwrap();
 
-    let ppm_info = PPMInfo {
-        filename: info.out_filename.clone(),
-        width: info.out_width,
-        height: info.out_height,
-        max_val: 255,
+        let name = format!("{}_crypto_{}", info.out_filename, layer.kind().name());
+        for (rank, image) in rank_images.into_iter().enumerate() {
+            let filename = format!("{}_{:02}", name, rank);
+            let image = region_output(info, image, || pfm::decode(&filename), || Color::new(0.0, 0.0, 0.0))?;
+            let pfm_info = PFMInfo {
+                filename,
+                width: out_width,
+                height: out_height
+            };
+            if let Err(e) = pfm::encode(&pfm_info, &image) {
+                return Err(format!("Error outputing cryptomatte: {}", e));
+            }
+        }
+        if let Err(e) = layer.write_manifest(&name) {
+            return Err(format!("Error outputing cryptomatte manifest: {}", e));
+        }
+    }
+
+    let result = match info.out_format {
+        ImageFormat::Ppm => {
+            let data = region_output(info, data, || ppm::decode(&info.out_filename), || Color::new(0.0, 0.0, 0.0))?;
+            let ppm_info = PPMInfo {
+                filename: info.out_filename.clone(),
+                width: out_width,
+                height: out_height,
+                max_val: 255,
+            };
+            ppm::encode(&ppm_info, data)
+        },
+        ImageFormat::Png => {
+            let image = data.into_iter().zip(alpha).collect();
+            let load = || png::decode(&info.out_filename).map(|(w, h, d, a)| (w, h, d.into_iter().zip(a).collect()));
+            let (data, alpha) = region_output(info, image, load, || (Color::new(0.0, 0.0, 0.0), 0.0))?.into_iter().unzip();
+            let png_info = PNGInfo {
+                filename: info.out_filename.clone(),
+                width: out_width,
+                height: out_height
+            };
+            png::encode(&png_info, data, alpha)
+        }
     };
 
-    match ppm::encode(&ppm_info, data) {
+    match result {
         Ok(_) => {
             return Ok(());
         },
@@ -466,19 +1267,54 @@ fn render_animation(info: &GeneralInfo, scene: &mut Scene,
         }
     };
 
+    let mut progress = Progress::new(end_frame + 1 - start_frame, info.time_limit);
     for frame in start_frame..(end_frame+1) {
-        println!("Rendering frame {}...", frame);
+        if interrupt::requested() {
+            progress.message(&format!("Stopped before frame {}.", frame));
+            break;
+        }
+        progress.message(&format!("Rendering frame {}...", frame));
         let temp_info = GeneralInfo {
             out_filename: format!("{}/{}_{}",info.out_filename, info.out_filename, frame),
             out_width: info.out_width,
             out_height: info.out_height,
             aa_sampling: info.aa_sampling,
+            unlimited_samples: info.unlimited_samples,
             ray_recursion: info.ray_recursion,
             threads: info.threads,
-            animation: info.animation
+            animation: info.animation,
+            spectral: info.spectral,
+            out_format: info.out_format,
+            transparent: info.transparent,
+            integrator: info.integrator,
+            bounces: info.bounces,
+            clamp: info.clamp,
+            photons: info.photons,
+            photon_radius: info.photon_radius,
+            photon_passes: info.photon_passes,
+            aovs: info.aovs.clone(),
+            cryptomatte: info.cryptomatte.clone(),
+            cryptomatte_ranks: info.cryptomatte_ranks,
+            denoise: info.denoise,
+            sampler: info.sampler,
+            seed: info.seed,
+            adaptive_threshold: info.adaptive_threshold,
+            min_samples: info.min_samples,
+            filter: info.filter,
+            tile_size: info.tile_size,
+            tile_order: info.tile_order,
+            pass_samples: info.pass_samples,
+            resume: info.resume,
+            time_limit: info.time_limit,
+            region: info.region,
+            crop: info.crop,
+            scene: info.scene,
+            sky: info.sky,
+            sky_end: info.sky_end,
+            voxels: info.voxels.clone()
         };
 
-        match render_still(&temp_info, scene, frame) {
+        match render_still(&temp_info, scene, frame, &mut progress) {
             Ok(_) => {},
             Err(e) => {
                 return Err(format!("Error rendering: {}", e));
@@ -489,11 +1325,33 @@ fn render_animation(info: &GeneralInfo, scene: &mut Scene,
     return Ok(());
 }
 
+/* The scene picked on the command line, seen through the camera */
+fn build_scene(info: &GeneralInfo) -> Result<Scene, String> {
+    let aspect_ratio = (info.out_width as f32) / (info.out_height as f32);
+    let mut camera = Camera::new()
+        .set_aspect_ratio(aspect_ratio)
+        .set_vertical_field_of_view(30.0)
+        .build();
+
+    camera.set_location(Vector3::new(4.0, 2.0, 4.0));
+    camera.set_rotation(Vector3::new(-10.0, 40.0, 0.0));
+    camera.set_focus_distance(5.7);
+    camera.set_aperture_size(info.scene.aperture());
+
+    let mut scene = info.scene.build(info.voxels.as_deref())?;
+    scene.add_camera(camera);
+    if let Some(sky) = info.sky {
+        scene.set_environment(Box::new(scenes::sky(sky, info.sky_end, FIRST_FRAME, LAST_FRAME)));
+    }
+    return Ok(scene);
+}
+
 fn main() {
     println!("Running Raybow...");
     let info_start_time = Instant::now();
 
-    let info = match get_info_from_args() {
+    let arguments: Vec<String> = args().collect();
+    let info = match get_info_from_args(&arguments) {
         Ok(f) => f,
         Err(e) => {
             eprintln!("{}", e);
@@ -501,6 +1359,11 @@ fn main() {
         }
     };
 
+    if let Err(e) = interrupt::install() {
+        eprintln!("{}", e);
+        return;
+    }
+
     let height = info.out_height;
     let width = info.out_width;
     let mut data: Vec<Color> = Vec::new();
@@ -508,25 +1371,16 @@ fn main() {
         data.push(Color::new(0.0, 0.0, 0.0));
     }
 
-    let aspect_ratio = (info.out_width as f32) / (info.out_height as f32);
-    let start_frame = 1;
-    let end_frame   = 23;
-
-    let mut camera = Camera::new()
-        .set_aspect_ratio(aspect_ratio)
-        .set_vertical_field_of_view(30.0)
-        .build();
-    
-    camera.set_location(Vector3::new(4.0, 2.0, 4.0));
-    camera.set_rotation(Vector3::new(-10.0, 40.0, 0.0));
-    camera.set_focus_distance(5.7);
-    camera.set_aperture_size(0.1);
-
-    let mut scene = test_scene2();
-    scene.add_camera(camera);
+    let mut scene = match build_scene(&info) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
 
     if info.animation {
-        match render_animation(&info, &mut scene, start_frame, end_frame) {
+        match render_animation(&info, &mut scene, FIRST_FRAME, LAST_FRAME) {
             Ok(_) => {
                 println!("Rendering finished successfully.");
             },
@@ -535,7 +1389,8 @@ fn main() {
             }
         }
     } else {
-        match render_still(&info, &mut scene, 1) {
+        let mut progress = Progress::new(1, info.time_limit);
+        match render_still(&info, &mut scene, 1, &mut progress) {
             Ok(_) => {
                 println!("Rendering finished successfully.");
             },
@@ -546,6 +1401,273 @@ fn main() {
     }
 
     let info_duration = info_start_time.elapsed();
+    stats::report(info_duration);
     println!("Finished in {:?}s.", info_duration.as_secs_f32());
     println!("Exiting...");
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use structures::environment::Environment;
+
+    /* Settings of a small render written to a file in the temporary directory */
+    fn test_info(name: &str, arguments: &str) -> GeneralInfo {
+        let filename = std::env::temp_dir().join(format!("raybow_test_{}", name));
+        let mut all = vec!["raybow".to_string(), "-o".to_string(), filename.to_str().unwrap().to_string()];
+        all.extend(arguments.split_whitespace().map(|a| a.to_string()));
+        return get_info_from_args(&all).unwrap();
+    }
+
+    /* Renders a still and reads the image back */
+    fn render_image(info: &GeneralInfo) -> Vec<Color> {
+        render_still(info, &mut build_scene(info).unwrap(), 1, &mut Progress::new(1, 0.0)).unwrap();
+        return ppm::decode(&info.out_filename).unwrap().2;
+    }
+
+    fn same_image(a: &[Color], b: &[Color]) -> bool {
+        return a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.r == b.r && a.g == b.g && a.b == b.b);
+    }
+
+    /* Average luminance of the pixels in a region before it's clamped for output, including
+     * the light image of the samples taken there */
+    fn mean_luminance(info: &GeneralInfo, region: &Tile) -> f64 {
+        let mut scene = build_scene(info).unwrap();
+        scene.update_transforms(1.0);
+        let mut integrator = info.integrator.create(info);
+        integrator.prepare(&scene, 1.0);
+        let camera = scene.get_camera(0).unwrap();
+
+        let mut sum = 0.0;
+        let mut samples = 0;
+        random::set_sampler(Some(info.sampler.create(info.aa_sampling, info.seed)));
+        for y in region.y_start..region.y_end {
+            for x in region.x_start..region.x_end {
+                for sample in 0..info.aa_sampling {
+                    random::start_pixel_sample(x, y, sample);
+                    let ray = camera.get_ray_at(x as f32 + random::random(), y as f32 + random::random(),
+                        info.out_width, info.out_height);
+                    sum += integrator.camera_ray_color(&scene, ray, 1.0).0.luminance() as f64;
+                    samples += 1;
+                }
+            }
+        }
+        random::set_sampler(None);
+
+        let pixels = (region.x_end - region.x_start) * (region.y_end - region.y_start);
+        let mut mean = sum / samples as f64;
+        if let Some(light) = integrator.light_image() {
+            let light = crop(light, info.out_width, region);
+            let scale = (info.out_width * info.out_height) as f64 / integrator.light_paths(samples) as f64;
+            mean += light.iter().map(|c| c.luminance() as f64).sum::<f64>() * scale / pixels as f64;
+        }
+        return mean;
+    }
+
+    #[test]
+    fn bidirectional_matches_path_tracing_through_glass() {
+        // The ground below the lamp in the glass globe, lit through the glass only
+        let ground = Tile { x_start: 0, x_end: 32, y_start: 10, y_end: 18 };
+        let path = mean_luminance(&test_info("glass_path", "-d 32 18 -s 1024 --scene glass"), &ground);
+        let bdpt = mean_luminance(&test_info("glass_bdpt", "-d 32 18 -s 512 --scene glass --integrator bdpt"), &ground);
+        assert!((bdpt / path - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn thread_count_does_not_change_the_image() {
+        let arguments = "-d 48 24 -s 8 --tile-size 8 --seed 7 --scene lights";
+        let one = render_image(&test_info("threads_1", &format!("{} -t 1", arguments)));
+        let many = render_image(&test_info("threads_4", &format!("{} -t 4", arguments)));
+        assert!(one.iter().any(|c| c.r > 0.0));
+        assert!(same_image(&one, &many));
+
+        // Photon maps are split over the threads as well
+        let arguments = "-d 32 16 -s 5 --scene glass --integrator photon --photons 2000";
+        let one = render_image(&test_info("photon_threads_1", &format!("{} -t 1", arguments)));
+        let many = render_image(&test_info("photon_threads_3", &format!("{} -t 3", arguments)));
+        assert!(same_image(&one, &many));
+    }
+
+    /* Pixels of an image inside the region given in the arguments of --region */
+    fn region_pixels(image: Vec<Color>, width: u64, region: &str) -> Vec<Color> {
+        let c: Vec<u64> = region.split_whitespace().map(|v| u64::from_str(v).unwrap()).collect();
+        return crop(image, width, &Tile { x_start: c[0], y_start: c[1], x_end: c[2], y_end: c[3] });
+    }
+
+    #[test]
+    fn region_coordinates_in_pixels_and_fractions() {
+        assert_eq!(region_coordinate("0", 400), Ok(0));
+        assert_eq!(region_coordinate("123", 400), Ok(123));
+        assert_eq!(region_coordinate("400", 400), Ok(400));
+        assert_eq!(region_coordinate("0.25", 400), Ok(100));
+        assert_eq!(region_coordinate("1.0", 200), Ok(200));
+        assert_eq!(region_coordinate(".5", 3), Ok(2));
+        assert!(region_coordinate("401", 400).is_err());
+        assert!(region_coordinate("1.5", 400).is_err());
+        assert!(region_coordinate("-0.5", 400).is_err());
+        assert!(region_coordinate("-1", 400).is_err());
+        assert!(region_coordinate("left", 400).is_err());
+    }
+
+    #[test]
+    fn region_arguments() {
+        let info = test_info("region_arguments", "-d 400 200 --region 0.25 10 300 0.5");
+        let region = info.region.unwrap();
+        assert_eq!((region.x_start, region.y_start, region.x_end, region.y_end), (100, 10, 300, 100));
+        assert!(!info.crop);
+
+        let all = |arguments: &str| {
+            let mut all = vec!["raybow".to_string()];
+            all.extend(arguments.split_whitespace().map(|a| a.to_string()));
+            get_info_from_args(&all)
+        };
+        assert!(all("-d 400 200 --region 10 10 10 20").is_err());
+        assert!(all("-d 400 200 --region 30 10 20 20").is_err());
+        assert!(all("-d 400 200 --region 0 0 500 20").is_err());
+        assert!(all("-d 400 200 --region 0 0 20").is_err());
+    }
+
+    #[test]
+    fn zero_threads_are_rejected() {
+        let all = vec!["raybow".to_string(), "-t".to_string(), "0".to_string()];
+        assert!(get_info_from_args(&all).is_err());
+    }
+
+    #[test]
+    fn photon_arguments() {
+        let info = test_info("photon_arguments", "-s 4 --photon-radius 0.1");
+        assert_eq!(info.photon_radius, 0.1);
+        assert_eq!(info.photon_passes(), 4);
+        assert_eq!(test_info("photon_arguments", "").photon_passes(), 16);
+        assert_eq!(test_info("photon_arguments", "-s 4 --photon-passes 30").photon_passes(), 30);
+
+        let all = |arguments: &str| {
+            let mut all = vec!["raybow".to_string()];
+            all.extend(arguments.split_whitespace().map(|a| a.to_string()));
+            get_info_from_args(&all)
+        };
+        assert!(all("--photon-radius 0").is_err());
+        assert!(all("--photon-radius -0.1").is_err());
+        assert!(all("--photon-passes 0").is_err());
+    }
+
+    #[test]
+    fn sky_moves_between_first_and_last_frame() {
+        let info = test_info("sky", "--sky 10 45 3 --sky-end 50 135 5");
+        assert_eq!(info.sky, Some(Sky { elevation: 10.0, azimuth: 45.0, turbidity: 3.0 }));
+
+        let mut scene = build_scene(&info).unwrap();
+        let directions = [Vector3::new(0.0, 1.0, 0.0), Vector3::new(1.0, 0.5, 0.0), Vector3::new(-1.0, 0.2, 1.0)];
+        let middle = structures::environments::preetham::PreethamSky::new(30.0, 90.0, 4.0);
+        scene.update_transforms(((FIRST_FRAME + LAST_FRAME) / 2) as f32);
+        for d in &directions {
+            let (a, b) = (scene.environment().color(d), middle.color(d));
+            assert!((a.luminance() - b.luminance()).abs() <= 1e-4 * b.luminance().max(1.0));
+        }
+
+        let all = |arguments: &str| {
+            let mut all = vec!["raybow".to_string()];
+            all.extend(arguments.split_whitespace().map(|a| a.to_string()));
+            get_info_from_args(&all)
+        };
+        assert!(all("--sky 10 45").is_err());
+        assert!(all("--sky 10 45 hazy").is_err());
+        assert!(all("--sky 100 45 3").is_err());
+        assert!(all("--sky 10 45 20").is_err());
+        assert!(all("--sky-end 10 45 3").is_err());
+    }
+
+    #[test]
+    fn crop_keeps_the_region_row_by_row() {
+        let image: Vec<u64> = (0..20).collect();
+        let region = Tile { x_start: 1, x_end: 3, y_start: 2, y_end: 4 };
+        assert_eq!(crop(image, 5, &region), vec![11, 12, 16, 17]);
+    }
+
+    #[test]
+    fn region_is_composited_into_earlier_render() {
+        let arguments = "-d 32 16 -s 4 --scene lights";
+        let region = "8 4 20 12";
+        let earlier = render_image(&test_info("composite", &format!("{} --seed 1", arguments)));
+        let other_seed = render_image(&test_info("composite_other", &format!("{} --seed 2", arguments)));
+        let composite = render_image(&test_info("composite", &format!("{} --seed 2 --region {}", arguments, region)));
+
+        let inside = |i: usize| (8..20).contains(&(i % 32)) && (4..12).contains(&(i / 32));
+        for (i, c) in composite.iter().enumerate() {
+            let expected = if inside(i) { &other_seed[i] } else { &earlier[i] };
+            assert_eq!((c.r, c.g, c.b), (expected.r, expected.g, expected.b));
+        }
+        assert!(!same_image(&earlier, &other_seed));
+    }
+
+    #[test]
+    fn region_without_earlier_render_is_surrounded_by_black() {
+        let info = test_info("composite_empty", "-d 24 12 -s 2 --scene lights --region 4 4 12 8");
+        let _ = fs::remove_file(format!("{}.ppm", info.out_filename));
+        let image = render_image(&info);
+        let full = render_image(&test_info("composite_empty_full", "-d 24 12 -s 2 --scene lights"));
+
+        // What the decoder turns a value of 0 into
+        let black = (0.5f32 / 256.0).powi(2);
+        for (i, c) in image.iter().enumerate() {
+            if (4..12).contains(&(i % 24)) && (4..8).contains(&(i / 24)) {
+                assert_eq!((c.r, c.g, c.b), (full[i].r, full[i].g, full[i].b));
+            } else {
+                assert_eq!((c.r, c.g, c.b), (black, black, black));
+            }
+        }
+    }
+
+    #[test]
+    fn metropolis_region_matches_full_render() {
+        let arguments = "-d 40 20 -s 4 -t 2 --seed 5 --scene lights --integrator mlt";
+        let full = render_image(&test_info("mlt_full", arguments));
+        let region = render_image(&test_info("mlt_region", &format!("{} --region 10 4 30 16 --crop", arguments)));
+        assert!(region.iter().any(|c| c.r > 0.0));
+        assert!(same_image(&region_pixels(full, 40, "10 4 30 16"), &region));
+    }
+
+    #[test]
+    fn metropolis_does_not_depend_on_threads() {
+        let arguments = "-d 16 8 -s 2 --seed 5 --scene lights --integrator mlt";
+        let one = render_image(&test_info("mlt_one_thread", &format!("{} -t 1", arguments)));
+        let three = render_image(&test_info("mlt_three_threads", &format!("{} -t 3", arguments)));
+        assert!(same_image(&one, &three));
+    }
+
+    #[test]
+    fn resume_with_other_settings_is_refused() {
+        let arguments = "-d 8 4 --pass-samples 2 -s 2 --scene lights";
+        render_image(&test_info("resume_settings", arguments));
+        for other in ["--integrator direct", "--spectral", "-r 3", "--clamp 5", "--transparent", "--sky 30 0 3", "--adaptive 0.1"] {
+            let info = test_info("resume_settings", &format!("{} -s 4 --resume {}", arguments, other));
+            assert!(render_still(&info, &mut build_scene(&info).unwrap(), 1, &mut Progress::new(1, 0.0)).is_err(), "{}", other);
+        }
+        render_image(&test_info("resume_settings", &format!("{} -s 4 --resume", arguments)));
+    }
+
+    #[test]
+    fn time_limit_without_sample_count_is_unlimited() {
+        assert!(test_info("time_limit_unlimited", "--time-limit 10").unlimited_samples);
+        assert!(!test_info("time_limit_limited", "--time-limit 10 -s 50").unlimited_samples);
+        assert!(!test_info("no_time_limit", "").unlimited_samples);
+        let all: Vec<String> = ["raybow", "--integrator", "mlt", "--time-limit", "10"].iter().map(|a| a.to_string()).collect();
+        assert!(get_info_from_args(&all).is_err());
+
+        // A tiny image takes far more than the default 50 samples in the time given
+        let info = test_info("time_limit_samples", "-d 4 2 --time-limit 0.5 --scene lights");
+        render_image(&info);
+        let mut state = RenderState::new(4, 2, info.filter, 0, 0);
+        let signature = checkpoint_signature(&info, 1, &[], &[]);
+        assert!(state.load(&format!("{}.checkpoint", info.out_filename), &signature).unwrap());
+        assert!(state.samples > 50);
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted_one() {
+        let arguments = "-d 32 16 --pass-samples 4 --seed 3 --scene lights";
+        let whole = render_image(&test_info("resume_whole", &format!("{} -s 8", arguments)));
+        render_image(&test_info("resume_part", &format!("{} -s 4", arguments)));
+        let resumed = render_image(&test_info("resume_part", &format!("{} -s 8 --resume", arguments)));
+        assert!(same_image(&whole, &resumed));
+    }
+}
@@ -14,6 +14,13 @@ impl Matrix3 {
         }
     }
 
+    pub fn transpose(&self) -> Self {
+        let m = &self.matrix;
+        Matrix3 {
+            matrix: vec![m[0], m[3], m[6], m[1], m[4], m[7], m[2], m[5], m[8]]
+        }
+    }
+
     pub fn rotation_matrix(x: f32, y: f32, z: f32) -> Self {
         let x = x.to_radians();
         let y = y.to_radians();
@@ -1,5 +1,4 @@
 use std::fmt::Display;
-use rand::{self, Rng};
 use std::ops;
 
 pub struct Vector3 {
@@ -106,20 +105,30 @@ impl Vector3 {
         }
     }
 
+    /** Uniformly distributed direction, a point on the surface of the unit sphere */
     pub fn random_in_unit_sphere() -> Self {
-        let a1: f32 = rand::prelude::thread_rng().sample(rand_distr::StandardNormal);
-        let a2: f32 = rand::prelude::thread_rng().sample(rand_distr::StandardNormal);
-        let a3: f32 = rand::prelude::thread_rng().sample(rand_distr::StandardNormal);
-
-        let norm = (a1*a1 + a2*a2 + a3*a3).sqrt();
+        let z = 1.0 - 2.0 * crate::random::random();
+        let r = (1.0 - z*z).max(0.0).sqrt();
+        let phi = 2.0 * std::f32::consts::PI * crate::random::random();
 
         Vector3 {
-            x: a1 / norm,
-            y: a2 / norm,
-            z: a3 / norm
+            x: r * phi.cos(),
+            y: r * phi.sin(),
+            z
         }
     }
 
+    /* Builds two tangents that, together with n, form an orthonormal basis. n should be normalized. */
+    pub fn orthonormal_basis(n: &Vector3) -> (Vector3, Vector3) {
+        let sign = 1.0_f32.copysign(n.z);
+        let a = -1.0 / (sign + n.z);
+        let b = n.x * n.y * a;
+
+        let t = Vector3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x);
+        let s = Vector3::new(b, sign + n.y * n.y * a, -n.y);
+        (t, s)
+    }
+
     /* Warning: i and n should be normalized!!! */
     pub fn refraction(i: &Vector3, n: &Vector3, c: f32) -> Vector3 {
         let cosa =  (&-i*n).min(1.0);
@@ -3,4 +3,22 @@ pub struct PPMInfo {
     pub width: u64,
     pub height: u64,
     pub max_val: u16
+}
+
+pub struct PNGInfo {
+    pub filename: String,
+    pub width: u64,
+    pub height: u64
+}
+
+pub struct PFMInfo {
+    pub filename: String,
+    pub width: u64,
+    pub height: u64
+}
+
+#[derive(Clone, Copy)]
+pub enum ImageFormat {
+    Ppm,
+    Png
 }
\ No newline at end of file
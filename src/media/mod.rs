@@ -1,2 +1,4 @@
 pub mod media_info;
-pub mod ppm;
\ No newline at end of file
+pub mod ppm;
+pub mod png;
+pub mod pfm;
\ No newline at end of file
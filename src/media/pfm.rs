@@ -0,0 +1,111 @@
+use std::{fs::{self, File}, io::{BufWriter, Error, ErrorKind, Write}};
+
+use crate::color::Color;
+
+use super::media_info::PFMInfo;
+
+/** Writes a 32-bit float RGB image without any gamma or clamping, for data
+ * that has to survive unchanged, like render passes */
+pub fn encode(info: &PFMInfo, data: &[Color]) -> Result<(), Error> {
+    let file_str = format!("{}.pfm", info.filename);
+    let file = File::create(&file_str)?;
+    let mut writer = BufWriter::new(file);
+
+    // A negative scale marks little endian data, rows are stored bottom to top
+    write!(writer, "PF\n{} {}\n-1.0\n", info.width, info.height)?;
+    for row in (0..info.height).rev() {
+        let start = (row * info.width) as usize;
+        for c in &data[start..start + info.width as usize] {
+            writer.write_all(&c.r.to_le_bytes())?;
+            writer.write_all(&c.g.to_le_bytes())?;
+            writer.write_all(&c.b.to_le_bytes())?;
+        }
+    }
+
+    writer.flush()
+}
+
+/** Reads a color image written by `encode` or another program, returning its width, height
+ * and colors */
+pub fn decode(filename: &str) -> Result<(u64, u64, Vec<Color>), Error> {
+    let file_str = format!("{}.pfm", filename);
+    let content = fs::read(&file_str)?;
+    let invalid = || Error::new(ErrorKind::InvalidData, format!("{} is not a color PFM image", file_str));
+
+    // Header of four tokens, the last followed by a single whitespace character before the data
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while tokens.len() < 4 {
+        while i < content.len() && content[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let start = i;
+        while i < content.len() && !content[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if start == i {
+            return Err(invalid());
+        }
+        tokens.push(String::from_utf8_lossy(&content[start..i]).to_string());
+    }
+    i += 1;
+
+    if tokens[0] != "PF" {
+        return Err(invalid());
+    }
+    let width = tokens[1].parse::<u64>().map_err(|_| invalid())?;
+    let height = tokens[2].parse::<u64>().map_err(|_| invalid())?;
+    let little_endian = tokens[3].parse::<f32>().map_err(|_| invalid())? < 0.0;
+
+    let count = (width * height) as usize;
+    if content.len() < i + count * 12 {
+        return Err(invalid());
+    }
+    let value = |j: usize| {
+        let bytes = [content[i + j * 4], content[i + j * 4 + 1], content[i + j * 4 + 2], content[i + j * 4 + 3]];
+        if little_endian { f32::from_le_bytes(bytes) } else { f32::from_be_bytes(bytes) }
+    };
+
+    let mut data = Vec::with_capacity(count);
+    for row in (0..height).rev() {
+        for x in 0..width {
+            let j = ((row * width + x) * 3) as usize;
+            data.push(Color::new(value(j), value(j + 1), value(j + 2)));
+        }
+    }
+    return Ok((width, height, data));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoded_image_is_unchanged() {
+        let filename = std::env::temp_dir().join("raybow_test_pfm").to_str().unwrap().to_string();
+        let info = PFMInfo { filename: filename.clone(), width: 7, height: 3 };
+        let data: Vec<Color> = (0..21).map(|i| Color::new(i as f32 * 0.37, -1.5, 1e6 / (i + 1) as f32)).collect();
+        encode(&info, &data).unwrap();
+
+        let (width, height, decoded) = decode(&filename).unwrap();
+        assert_eq!((width, height), (7, 3));
+        for (a, b) in data.iter().zip(decoded.iter()) {
+            assert_eq!((a.r, a.g, a.b), (b.r, b.g, b.b));
+        }
+    }
+
+    #[test]
+    fn big_endian_images_are_read() {
+        let filename = std::env::temp_dir().join("raybow_test_pfm_big_endian").to_str().unwrap().to_string();
+        let mut content = b"PF\n1 2\n1.0\n".to_vec();
+        for v in [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0] {
+            content.extend_from_slice(&v.to_be_bytes());
+        }
+        fs::write(format!("{}.pfm", filename), content).unwrap();
+
+        // Rows are stored bottom to top
+        let (_, _, decoded) = decode(&filename).unwrap();
+        assert_eq!((decoded[0].r, decoded[0].g, decoded[0].b), (4.0, 5.0, 6.0));
+        assert_eq!((decoded[1].r, decoded[1].g, decoded[1].b), (1.0, 2.0, 3.0));
+    }
+}
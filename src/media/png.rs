@@ -0,0 +1,87 @@
+use std::{fs::File, io::{BufWriter, Error, ErrorKind}};
+
+use crate::color::Color;
+
+use super::media_info::PNGInfo;
+
+/** Writes an 8-bit RGBA image. Colors are expected premultiplied by alpha. */
+pub fn encode(info: &PNGInfo, data: Vec<Color>, alpha: Vec<f32>) -> Result<(), Error> {
+    let file_str = format!("{}.png", info.filename);
+    let file = File::create(&file_str)?;
+    let writer = BufWriter::new(file);
+
+    let mut encoder = png::Encoder::new(writer, info.width as u32, info.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut content: Vec<u8> = Vec::with_capacity(data.len() * 4);
+    for (c, a) in data.iter().zip(alpha.iter()) {
+        let a = a.clamp(0.0, 1.0);
+        let mut straight = if a > 0.0 {
+            Color::scale(c, 1.0 / a)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+        straight.clamp();
+        content.extend_from_slice(&straight.to_uint8());
+        content.push((a * 255.999) as u8);
+    }
+
+    let mut png_writer = encoder.write_header()
+        .map_err(|e| Error::new(ErrorKind::Other, e))?;
+    png_writer.write_image_data(&content)
+        .map_err(|e| Error::new(ErrorKind::Other, e))
+}
+
+/** Reads an 8-bit RGB or RGBA image, returning its width, height, colors premultiplied by
+ * alpha and the alpha. The gamma is undone, so that `encode` gives the same values again. */
+pub fn decode(filename: &str) -> Result<(u64, u64, Vec<Color>, Vec<f32>), Error> {
+    let file_str = format!("{}.png", filename);
+    let file = File::open(&file_str)?;
+
+    let decoder = png::Decoder::new(file);
+    let mut reader = decoder.read_info()
+        .map_err(|e| Error::new(ErrorKind::Other, e))?;
+    let mut content = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut content)
+        .map_err(|e| Error::new(ErrorKind::Other, e))?;
+
+    let channels = match (frame.color_type, frame.bit_depth) {
+        (png::ColorType::Rgba, png::BitDepth::Eight) => 4,
+        (png::ColorType::Rgb, png::BitDepth::Eight) => 3,
+        _ => {
+            return Err(Error::new(ErrorKind::InvalidData, format!("{} is not an 8-bit RGB(A) image", file_str)));
+        }
+    };
+
+    let to_float = |v: u8| (v as f32 + 0.5) / 256.0;
+    let mut data = Vec::with_capacity((frame.width * frame.height) as usize);
+    let mut alpha = Vec::with_capacity((frame.width * frame.height) as usize);
+    for pixel in content[..frame.buffer_size()].chunks_exact(channels) {
+        let a = if channels == 4 { to_float(pixel[3]) } else { 1.0 };
+        let c = Color::new(to_float(pixel[0]).powi(2), to_float(pixel[1]).powi(2), to_float(pixel[2]).powi(2));
+        data.push(Color::scale(&c, a));
+        alpha.push(a);
+    }
+    return Ok((frame.width as u64, frame.height as u64, data, alpha));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoded_image_encodes_the_same() {
+        let filename = std::env::temp_dir().join("raybow_test_png").to_str().unwrap().to_string();
+        let info = PNGInfo { filename: filename.clone(), width: 7, height: 3 };
+        let alpha: Vec<f32> = (0..21).map(|i| (i % 5) as f32 / 4.0).collect();
+        let data = (0..21).map(|i| Color::scale(&Color::new(i as f32 / 20.0, 0.5, (i % 4) as f32 / 3.0), alpha[i])).collect();
+        encode(&info, data, alpha).unwrap();
+        let written = std::fs::read(format!("{}.png", filename)).unwrap();
+
+        let (width, height, decoded, decoded_alpha) = decode(&filename).unwrap();
+        assert_eq!((width, height), (7, 3));
+        encode(&info, decoded, decoded_alpha).unwrap();
+        assert_eq!(std::fs::read(format!("{}.png", filename)).unwrap(), written);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fs, io::Error};
+use std::{fs, io::{Error, ErrorKind}};
 
 use crate::color::Color;
 
@@ -13,4 +13,59 @@ pub fn encode(info: &PPMInfo, data: Vec<Color>) -> Result<(), Error> {
     }
 
     fs::write(&file_str, content)
-}
\ No newline at end of file
+}
+
+/** Reads an image written by `encode`, returning its width, height and colors. The gamma is
+ * undone, so that the colors are encoded into the same values again. */
+pub fn decode(filename: &str) -> Result<(u64, u64, Vec<Color>), Error> {
+    let file_str = format!("{}.ppm", filename);
+    let content = fs::read_to_string(&file_str)?;
+    let invalid = || Error::new(ErrorKind::InvalidData, format!("{} is not a P3 image", file_str));
+
+    let mut tokens = content.split_whitespace();
+    if tokens.next() != Some("P3") {
+        return Err(invalid());
+    }
+    let mut numbers = tokens.map(|t| t.parse::<u64>().map_err(|_| invalid()));
+    let width = numbers.next().ok_or_else(invalid)??;
+    let height = numbers.next().ok_or_else(invalid)??;
+    let max_val = numbers.next().ok_or_else(invalid)?? as f32;
+
+    let mut data = Vec::with_capacity((width * height) as usize);
+    let mut channel = || -> Result<f32, Error> {
+        let v = numbers.next().ok_or_else(invalid)?? as f32;
+        return Ok(((v + 0.5) / (max_val + 1.0)).powi(2));
+    };
+    for _ in 0..width * height {
+        data.push(Color::new(channel()?, channel()?, channel()?));
+    }
+    return Ok((width, height, data));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoded_image_encodes_the_same() {
+        let filename = std::env::temp_dir().join("raybow_test_ppm").to_str().unwrap().to_string();
+        let info = PPMInfo { filename: filename.clone(), width: 7, height: 3, max_val: 255 };
+        let data = (0..21).map(|i| Color::new(i as f32 / 20.0, 1.0 - i as f32 / 20.0, (i % 4) as f32 / 3.0)).collect();
+        encode(&info, data).unwrap();
+        let written = fs::read(format!("{}.ppm", filename)).unwrap();
+
+        let (width, height, decoded) = decode(&filename).unwrap();
+        assert_eq!((width, height), (7, 3));
+        encode(&info, decoded).unwrap();
+        assert_eq!(fs::read(format!("{}.ppm", filename)).unwrap(), written);
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let filename = std::env::temp_dir().join("raybow_test_ppm_invalid").to_str().unwrap().to_string();
+        fs::write(format!("{}.ppm", filename), "P6\n2 2\n255\n").unwrap();
+        assert!(matches!(decode(&filename), Err(e) if e.kind() == ErrorKind::InvalidData));
+        fs::write(format!("{}.ppm", filename), "P3\n2 2\n255\n1 2 3").unwrap();
+        assert!(matches!(decode(&filename), Err(e) if e.kind() == ErrorKind::InvalidData));
+    }
+}
@@ -0,0 +1,143 @@
+use std::{io::{IsTerminal, Write}, sync::Mutex, time::Instant};
+
+/* Seconds between updates of the progress bar */
+const UPDATE_INTERVAL: f32 = 0.25;
+/* Width of the progress bar in characters */
+const BAR_WIDTH: usize = 30;
+
+/** Progress of a render, shown as a bar with the time left for the current frame and, in
+ * animations, for all of them. Without a terminal, a line is printed every 10% instead. */
+pub struct Progress {
+    frames: u64,
+    frames_done: u64,
+    time_limit: f32,
+    start: Instant,
+    frame_start: Instant,
+    frame_start_done: f32,
+    terminal: bool,
+    /* Time of the last update and the last tenth printed without a terminal */
+    last: Mutex<(Instant, u64)>
+}
+
+impl Progress {
+    /** Progress of rendering the given number of frames, each stopped after `time_limit`
+     * seconds if that's positive */
+    pub fn new(frames: u64, time_limit: f32) -> Self {
+        let now = Instant::now();
+        Progress {
+            frames: frames.max(1),
+            frames_done: 0,
+            time_limit,
+            start: now,
+            frame_start: now,
+            frame_start_done: 0.0,
+            terminal: std::io::stdout().is_terminal(),
+            last: Mutex::new((now, 0))
+        }
+    }
+
+    /** Starts timing the next frame, with the given part of it already done, e.g. by a
+     * resumed render */
+    pub fn start_frame(&mut self, done: f32) {
+        self.frame_start = Instant::now();
+        self.frame_start_done = done;
+        *self.last.lock().unwrap() = (self.frame_start, (done * 10.0) as u64);
+    }
+
+    /** Ends the line of the progress bar once the frame is done, or stopped with the given
+     * part of it done */
+    pub fn finish_frame(&mut self, done: f32) {
+        self.update(done, true);
+        if self.terminal {
+            println!();
+        }
+        self.frames_done += 1;
+    }
+
+    /** Prints a line of text in place of the progress bar, which is drawn again with the next update */
+    pub fn message(&self, text: &str) {
+        if self.terminal {
+            print!("\r\x1b[K");
+        }
+        println!("{}", text);
+    }
+
+    /** Shows that the given part of the current frame is done. Updates are only shown every
+     * so often, unless forced. */
+    pub fn update(&self, done: f32, force: bool) {
+        let done = done.clamp(0.0, 1.0);
+        let mut last = match self.last.try_lock() {
+            Ok(l) => l,
+            Err(_) => {
+                if !force {
+                    return;
+                }
+                self.last.lock().unwrap()
+            }
+        };
+
+        let tenth = (done * 10.0) as u64;
+        if self.terminal {
+            if !force && last.0.elapsed().as_secs_f32() < UPDATE_INTERVAL {
+                return;
+            }
+        } else if tenth <= last.1 {
+            return;
+        }
+        *last = (Instant::now(), tenth);
+
+        let line = self.status(done);
+        if self.terminal {
+            print!("\r{}\x1b[K", line);
+            let _ = std::io::stdout().flush();
+        } else {
+            println!("{}", line);
+        }
+    }
+
+    /* Progress bar, percentage and time left */
+    fn status(&self, done: f32) -> String {
+        let frame_elapsed = self.frame_start.elapsed().as_secs_f32();
+        let mut frame_left = if done >= 1.0 {
+            0.0
+        } else if done > self.frame_start_done {
+            frame_elapsed * (1.0 - done) / (done - self.frame_start_done)
+        } else {
+            f32::INFINITY
+        };
+        if self.time_limit > 0.0 {
+            frame_left = frame_left.min((self.time_limit - frame_elapsed).max(0.0));
+        }
+
+        let filled = ((done * BAR_WIDTH as f32) as usize).min(BAR_WIDTH);
+        let mut status = format!("[{}{}] {:5.1}%, {} left", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled),
+            done * 100.0, format_time(frame_left));
+
+        if self.frames > 1 {
+            let total_done = (self.frames_done as f32 + done) / self.frames as f32;
+            let total_left = if total_done > 0.0 {
+                self.start.elapsed().as_secs_f32() * (1.0 - total_done) / total_done
+            } else {
+                f32::INFINITY
+            };
+            status = format!("Frame {}/{} {}, all frames {} left", self.frames_done + 1, self.frames,
+                status, format_time(total_left));
+        }
+        return status;
+    }
+}
+
+/** Seconds as hours, minutes and seconds, e.g. "1h 02m 03s" */
+pub fn format_time(seconds: f32) -> String {
+    if !seconds.is_finite() {
+        return "?".to_string();
+    }
+
+    let s = seconds.round() as u64;
+    if s >= 3600 {
+        return format!("{}h {:02}m {:02}s", s / 3600, s / 60 % 60, s % 60);
+    } else if s >= 60 {
+        return format!("{}m {:02}s", s / 60, s % 60);
+    }
+    return format!("{}s", s);
+}
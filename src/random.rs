@@ -0,0 +1,41 @@
+use std::cell::RefCell;
+
+use crate::samplers::sampler::Sampler;
+
+/* The sampler of each render thread. It's installed per thread rather than passed to the camera,
+ * the materials and the integrators, which keeps `Material::scatter`, `Camera::get_ray` and the
+ * vector helpers free of a sampler argument. Whoever starts a thread's work installs it: the tile
+ * renderer the one chosen with --sampler, photon mapping an independent one per pass, and
+ * Metropolis light transport the primary samples of its chains, which is how it mutates paths. */
+thread_local! {
+    static SAMPLER: RefCell<Option<Box<dyn Sampler>>> = RefCell::new(None);
+}
+
+/** Uniform random number in [0, 1). Every random decision made while rendering goes through
+ * here, so the renderer or an integrator can take control of them with `set_sampler`. Without
+ * a sampler the thread's random number generator is used. */
+pub fn random() -> f32 {
+    return SAMPLER.with(|sampler| {
+        match sampler.borrow_mut().as_mut() {
+            Some(s) => s.next(),
+            None => rand::random()
+        }
+    });
+}
+
+/** Makes `random` draw from the given sampler on the current thread, or from the random number
+ * generator again with None. The sampler must not call `random` itself. */
+pub fn set_sampler(sampler: Option<Box<dyn Sampler>>) {
+    SAMPLER.with(|s| {
+        *s.borrow_mut() = sampler;
+    });
+}
+
+/** Tells the sampler of the current thread that a new sample of a pixel starts */
+pub fn start_pixel_sample(x: u64, y: u64, index: u64) {
+    SAMPLER.with(|sampler| {
+        if let Some(s) = sampler.borrow_mut().as_mut() {
+            s.start_pixel_sample(x, y, index);
+        }
+    });
+}
@@ -2,14 +2,24 @@ use crate::math::vector3::Vector3;
 
 pub struct Ray {
     origin: Vector3,
-    direction: Vector3
+    direction: Vector3,
+    wavelength: Option<f32>
 }
 
 impl Ray {
     pub fn new(orig: Vector3, dir: Vector3) -> Self {
         Ray {
             origin: orig,
-            direction: dir
+            direction: dir,
+            wavelength: None
+        }
+    }
+
+    pub fn copy(&self) -> Self {
+        Ray {
+            origin: self.origin.copy(),
+            direction: self.direction.copy(),
+            wavelength: self.wavelength
         }
     }
 
@@ -21,6 +31,15 @@ impl Ray {
         &self.direction
     }
 
+    /** Wavelength in nanometers carried by the ray in spectral mode, None in RGB mode */
+    pub fn get_wavelength(&self) -> Option<f32> {
+        self.wavelength
+    }
+
+    pub fn set_wavelength(&mut self, wavelength: Option<f32>) {
+        self.wavelength = wavelength;
+    }
+
     pub fn at(&self, t: f32) -> Vector3 {
         let dt = Vector3::scale(&self.direction, t);
         let p = Vector3::sum(&self.origin, &dt);
@@ -0,0 +1,139 @@
+use std::sync::OnceLock;
+
+use super::{sampler::{Sampler, hash, mix, fraction_to_float}, sobol::Sobol};
+
+/* Side of the tileable blue noise mask */
+const SIZE: usize = 64;
+/* Standard deviation of the energy function of the void-and-cluster method */
+const SIGMA: f32 = 1.5;
+
+static MASK: OnceLock<Vec<f32>> = OnceLock::new();
+
+/** Screen space blue noise (Georgiev and Fajardo). Every pixel uses the same Sobol points,
+ * shifted by a value of a blue noise mask: neighbouring pixels get very different shifts,
+ * so what error is left looks like fine grain instead of blotches. Every dimension reads
+ * the mask at its own offset. */
+pub struct BlueNoise {
+    mask: &'static [f32],
+    seed: u64,
+    x: u64,
+    y: u64,
+    index: u64,
+    dimension: u64
+}
+
+impl BlueNoise {
+    pub fn new(seed: u64) -> Self {
+        BlueNoise {
+            mask: MASK.get_or_init(void_and_cluster),
+            seed,
+            x: 0, y: 0, index: 0, dimension: 0
+        }
+    }
+}
+
+impl Sampler for BlueNoise {
+    fn start_pixel_sample(&mut self, x: u64, y: u64, index: u64) {
+        self.x = x;
+        self.y = y;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next(&mut self) -> f32 {
+        let d = self.dimension;
+        self.dimension += 1;
+
+        // The points must be the same for all pixels, so the scrambling only depends on the dimension
+        let pair_seed = hash(self.seed, 0, 0, u64::MAX, d / 2) as u32;
+        let index = Sobol::nested_uniform_scramble(self.index as u32, pair_seed);
+        let scramble_seed = hash(self.seed, 0, 0, u64::MAX - 1, d) as u32;
+        let value = fraction_to_float(Sobol::nested_uniform_scramble(Sobol::sample(index, d % 2), scramble_seed));
+
+        let offset = hash(self.seed, 0, 0, 0, d);
+        let mx = self.x.wrapping_add(offset) as usize % SIZE;
+        let my = self.y.wrapping_add(offset >> 32) as usize % SIZE;
+        let shifted = value + self.mask[my * SIZE + mx];
+        return (shifted - shifted.floor()).min(1.0 - f32::EPSILON);
+    }
+}
+
+/* Builds a blue noise mask with Ulichney's void-and-cluster method: pixels are ranked in the
+ * order they're added to a point pattern, always filling the largest void, so the pixels
+ * below any threshold are spread evenly. Ranks are returned as values in [0, 1). */
+fn void_and_cluster() -> Vec<f32> {
+    let n = SIZE * SIZE;
+
+    // Gaussian of the toroidal distance between two pixels
+    let mut kernel = vec![0.0; n];
+    for dy in 0..SIZE {
+        for dx in 0..SIZE {
+            let x = dx.min(SIZE - dx) as f32;
+            let y = dy.min(SIZE - dy) as f32;
+            kernel[dy * SIZE + dx] = (-(x * x + y * y) / (2.0 * SIGMA * SIGMA)).exp();
+        }
+    }
+
+    let mut pattern = vec![false; n];
+    let mut energy = vec![0.0; n];
+    let toggle = |pattern: &mut Vec<bool>, energy: &mut Vec<f32>, p: usize| {
+        pattern[p] = !pattern[p];
+        let sign = if pattern[p] { 1.0 } else { -1.0 };
+        let (px, py) = (p % SIZE, p / SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let k = ((y + SIZE - py) % SIZE) * SIZE + (x + SIZE - px) % SIZE;
+                energy[y * SIZE + x] += sign * kernel[k];
+            }
+        }
+    };
+    // Tightest cluster among the set pixels, largest void among the others
+    let tightest = |pattern: &[bool], energy: &[f32]| -> usize {
+        (0..n).filter(|p| pattern[*p]).max_by(|a, b| energy[*a].total_cmp(&energy[*b])).unwrap()
+    };
+    let largest_void = |pattern: &[bool], energy: &[f32]| -> usize {
+        (0..n).filter(|p| !pattern[*p]).min_by(|a, b| energy[*a].total_cmp(&energy[*b])).unwrap()
+    };
+
+    // Random initial pattern, relaxed by moving points from clusters to voids
+    let initial = n / 10;
+    let mut state = 0;
+    let mut placed = 0;
+    while placed < initial {
+        state = mix(state + 1);
+        let p = (state % n as u64) as usize;
+        if !pattern[p] {
+            toggle(&mut pattern, &mut energy, p);
+            placed += 1;
+        }
+    }
+    loop {
+        let cluster = tightest(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, cluster);
+        let void = largest_void(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; n];
+
+    // Points of the initial pattern are ranked by removing clusters
+    let (mut ones, mut ones_energy) = (pattern.clone(), energy.clone());
+    for r in (0..initial).rev() {
+        let cluster = tightest(&ones, &ones_energy);
+        toggle(&mut ones, &mut ones_energy, cluster);
+        rank[cluster] = r;
+    }
+
+    // The rest by filling voids. Past half of the pixels this is the same as taking the tightest
+    // cluster of the unset pixels, since their energy is the total minus the energy of the set ones.
+    for r in initial..n {
+        let void = largest_void(&pattern, &energy);
+        toggle(&mut pattern, &mut energy, void);
+        rank[void] = r;
+    }
+
+    return rank.iter().map(|r| (*r as f32 + 0.5) / n as f32).collect();
+}
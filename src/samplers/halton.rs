@@ -0,0 +1,73 @@
+use super::sampler::{Sampler, hash, mix, to_float, permutation_element};
+
+/** Halton sequence, the radical inverse of the sample index in a different prime base for
+ * every dimension. The digits are shuffled with random permutations, picked anew for every
+ * pixel and dimension, which breaks up the correlation between dimensions with large bases
+ * and keeps neighbouring pixels from sharing their patterns. Dimensions past the table of
+ * primes fall back to white noise. */
+pub struct Halton {
+    primes: Vec<u64>,
+    seed: u64,
+    x: u64,
+    y: u64,
+    index: u64,
+    dimension: u64
+}
+
+impl Halton {
+    const DIMENSIONS: usize = 128;
+
+    pub fn new(seed: u64) -> Self {
+        let mut primes = Vec::with_capacity(Halton::DIMENSIONS);
+        let mut candidate = 2;
+        while primes.len() < Halton::DIMENSIONS {
+            if primes.iter().take_while(|p| *p * *p <= candidate).all(|p| candidate % p != 0) {
+                primes.push(candidate);
+            }
+            candidate += 1;
+        }
+
+        Halton {
+            primes, seed,
+            x: 0, y: 0, index: 0, dimension: 0
+        }
+    }
+
+    /* Radical inverse with every digit permuted. The leading zeros of the index are permuted
+     * too, so digits continue up to the precision of a float. */
+    fn scrambled_radical_inverse(base: u64, mut a: u64, seed: u64) -> f32 {
+        let inv_base = 1.0 / base as f64;
+        let mut factor = inv_base;
+        let mut result = 0.0;
+        let mut digit_index = 0;
+        while factor > 1e-8 {
+            let digit = permutation_element((a % base) as u32, base as u32, mix(seed ^ digit_index) as u32);
+            result += digit as f64 * factor;
+            a /= base;
+            factor *= inv_base;
+            digit_index += 1;
+        }
+        return (result as f32).min(1.0 - f32::EPSILON);
+    }
+}
+
+impl Sampler for Halton {
+    fn start_pixel_sample(&mut self, x: u64, y: u64, index: u64) {
+        self.x = x;
+        self.y = y;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next(&mut self) -> f32 {
+        let d = self.dimension;
+        self.dimension += 1;
+
+        if d as usize >= self.primes.len() {
+            return to_float(hash(self.seed, self.x, self.y, self.index, d));
+        }
+
+        let seed = hash(self.seed, self.x, self.y, u64::MAX, d);
+        return Halton::scrambled_radical_inverse(self.primes[d as usize], self.index, seed);
+    }
+}
@@ -0,0 +1,33 @@
+use super::sampler::{Sampler, hash, to_float};
+
+/** Uniform white noise, every dimension of every sample is independent of the others */
+pub struct Independent {
+    seed: u64,
+    x: u64,
+    y: u64,
+    index: u64,
+    dimension: u64
+}
+
+impl Independent {
+    pub fn new(seed: u64) -> Self {
+        Independent {
+            seed, x: 0, y: 0, index: 0, dimension: 0
+        }
+    }
+}
+
+impl Sampler for Independent {
+    fn start_pixel_sample(&mut self, x: u64, y: u64, index: u64) {
+        self.x = x;
+        self.y = y;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next(&mut self) -> f32 {
+        let h = hash(self.seed, self.x, self.y, self.index, self.dimension);
+        self.dimension += 1;
+        return to_float(h);
+    }
+}
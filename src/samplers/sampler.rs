@@ -0,0 +1,125 @@
+use super::{independent::Independent, stratified::Stratified, halton::Halton, sobol::Sobol, blue_noise::BlueNoise};
+
+/** Source of the uniform random numbers used to render a pixel sample. Each number drawn
+ * for a sample is one dimension of it: the first two place it in the pixel, the next ones
+ * pick a point on the lens, then come the choices made at every bounce of the path.
+ * Samplers that know the dimension can spread the samples of a pixel evenly. The renderer
+ * installs one per thread with `random::set_sampler`. */
+pub trait Sampler {
+    /** Starts the given sample of a pixel, the next number drawn is its first dimension */
+    fn start_pixel_sample(&mut self, _x: u64, _y: u64, _index: u64) {}
+
+    /** Next dimension of the current sample, in [0, 1) */
+    fn next(&mut self) -> f32;
+}
+
+#[derive(Clone, Copy)]
+pub enum SamplerType {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise
+}
+
+impl SamplerType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "independent" => Some(SamplerType::Independent),
+            "stratified" => Some(SamplerType::Stratified),
+            "halton" => Some(SamplerType::Halton),
+            "sobol" => Some(SamplerType::Sobol),
+            "bluenoise" => Some(SamplerType::BlueNoise),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SamplerType::Independent => "independent",
+            SamplerType::Stratified => "stratified",
+            SamplerType::Halton => "halton",
+            SamplerType::Sobol => "sobol",
+            SamplerType::BlueNoise => "bluenoise"
+        }
+    }
+
+    /** Creates a sampler for pixels taking the given number of samples. Samplers with
+     * different seeds, e.g. for different frames, produce different noise. */
+    pub fn create(&self, samples_per_pixel: u64, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerType::Independent => Box::new(Independent::new(seed)),
+            SamplerType::Stratified => Box::new(Stratified::new(samples_per_pixel, seed)),
+            SamplerType::Halton => Box::new(Halton::new(seed)),
+            SamplerType::Sobol => Box::new(Sobol::new(seed)),
+            SamplerType::BlueNoise => Box::new(BlueNoise::new(seed))
+        }
+    }
+}
+
+/** Scrambles the bits of a value (the SplitMix64 finalizer) */
+pub fn mix(mut x: u64) -> u64 {
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xbf58476d1ce4e5b9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94d049bb133111eb);
+    x ^= x >> 31;
+    return x;
+}
+
+/** Seed of the random numbers of a frame rendered with the given seed */
+pub fn frame_seed(seed: u64, frame: u64) -> u64 {
+    return mix(mix(seed) ^ frame);
+}
+
+/** Hash of a pixel sample and dimension, the seed of any randomness tied to them */
+pub fn hash(seed: u64, x: u64, y: u64, index: u64, dimension: u64) -> u64 {
+    return mix(mix(mix(mix(mix(seed) ^ x) ^ y) ^ index) ^ dimension);
+}
+
+/** Turns the high bits of a hash into a float in [0, 1) */
+pub fn to_float(h: u64) -> f32 {
+    return (h >> 40) as f32 / (1u64 << 24) as f32;
+}
+
+/** Float in [0, 1) from the bits of a 32-bit fixed point fraction */
+pub fn fraction_to_float(bits: u32) -> f32 {
+    return (bits >> 8) as f32 / (1u32 << 24) as f32;
+}
+
+/** Element `i` of a random permutation of 0..n picked by the seed (Kensler's
+ * "Correlated Multi-Jittered Sampling"), without having to store the permutation */
+pub fn permutation_element(mut i: u32, n: u32, seed: u32) -> u32 {
+    let mut w = n.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+
+    return i.wrapping_add(seed) % n;
+}
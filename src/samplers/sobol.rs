@@ -0,0 +1,76 @@
+use super::sampler::{Sampler, hash, fraction_to_float};
+
+/** Padded Owen-scrambled Sobol sequence (Burley, "Practical Hash-based Owen Scrambling").
+ * Dimensions are taken in pairs from the first two dimensions of the Sobol sequence, which
+ * are well stratified together. Every pair shuffles the order of the samples and scrambles
+ * its points with its own seed, so pairs don't correlate with each other. */
+pub struct Sobol {
+    seed: u64,
+    x: u64,
+    y: u64,
+    index: u64,
+    dimension: u64
+}
+
+impl Sobol {
+    pub fn new(seed: u64) -> Self {
+        Sobol {
+            seed,
+            x: 0, y: 0, index: 0, dimension: 0
+        }
+    }
+
+    /** Dimension 0 or 1 of the Sobol sequence as a fixed point fraction */
+    pub fn sample(index: u32, dimension: u64) -> u32 {
+        if dimension == 0 {
+            return index.reverse_bits();
+        }
+
+        let mut v = 1u32 << 31;
+        let mut result = 0;
+        let mut i = index;
+        while i != 0 {
+            if i & 1 != 0 {
+                result ^= v;
+            }
+            i >>= 1;
+            v ^= v >> 1;
+        }
+        return result;
+    }
+
+    /** Owen scrambling of the bits of a fixed point fraction */
+    pub fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+        return Sobol::laine_karras(x.reverse_bits(), seed).reverse_bits();
+    }
+
+    /* Hash where every bit only depends on the bits below it */
+    fn laine_karras(mut x: u32, seed: u32) -> u32 {
+        x = x.wrapping_add(seed);
+        x ^= x.wrapping_mul(0x6c50b47c);
+        x ^= x.wrapping_mul(0xb82f1e52);
+        x ^= x.wrapping_mul(0xc7afe638);
+        x ^= x.wrapping_mul(0x8d22f6e6);
+        return x;
+    }
+}
+
+impl Sampler for Sobol {
+    fn start_pixel_sample(&mut self, x: u64, y: u64, index: u64) {
+        self.x = x;
+        self.y = y;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next(&mut self) -> f32 {
+        let d = self.dimension;
+        self.dimension += 1;
+
+        let pair_seed = hash(self.seed, self.x, self.y, u64::MAX, d / 2) as u32;
+        let index = Sobol::nested_uniform_scramble(self.index as u32, pair_seed);
+        let value = Sobol::sample(index, d % 2);
+        let scramble_seed = hash(self.seed, self.x, self.y, u64::MAX - 1, d) as u32;
+        return fraction_to_float(Sobol::nested_uniform_scramble(value, scramble_seed));
+    }
+}
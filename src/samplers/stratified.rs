@@ -0,0 +1,56 @@
+use super::sampler::{Sampler, hash, to_float, permutation_element};
+
+/** Jittered stratification. Pairs of dimensions are split into a grid with a cell for every
+ * sample, and each sample of a pixel lands in a different cell of every pair, in random order.
+ * Samples past the expected count fall back to white noise. */
+pub struct Stratified {
+    samples: u64,
+    nx: u64,
+    ny: u64,
+    seed: u64,
+    x: u64,
+    y: u64,
+    index: u64,
+    dimension: u64
+}
+
+impl Stratified {
+    pub fn new(samples: u64, seed: u64) -> Self {
+        let samples = samples.max(1);
+        let nx = ((samples as f64).sqrt() as u64).max(1);
+        let ny = samples.div_ceil(nx);
+        Stratified {
+            samples, nx, ny, seed,
+            x: 0, y: 0, index: 0, dimension: 0
+        }
+    }
+}
+
+impl Sampler for Stratified {
+    fn start_pixel_sample(&mut self, x: u64, y: u64, index: u64) {
+        self.x = x;
+        self.y = y;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next(&mut self) -> f32 {
+        let d = self.dimension;
+        self.dimension += 1;
+
+        let jitter = to_float(hash(self.seed, self.x, self.y, self.index, d));
+        if self.index >= self.samples {
+            return jitter;
+        }
+
+        // Both dimensions of a pair have to agree on the cell
+        let pair_seed = hash(self.seed, self.x, self.y, u64::MAX, d / 2) as u32;
+        let cell = permutation_element(self.index as u32, (self.nx * self.ny) as u32, pair_seed) as u64;
+        let value = if d % 2 == 0 {
+            ((cell % self.nx) as f32 + jitter) / self.nx as f32
+        } else {
+            ((cell / self.nx) as f32 + jitter) / self.ny as f32
+        };
+        return value.min(1.0 - f32::EPSILON);
+    }
+}
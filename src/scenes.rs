@@ -0,0 +1,348 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{animation::animation::{AnimationChannel, AnimationKey, Interpolation}, color::Color, math::{vector2::Vector2, vector3::Vector3},
+    structures::{scene::Scene, node::Node, material::Material, sphere::Sphere, material_library::MaterialId, environments::{gradient::Gradient, preetham::PreethamSky},
+    volumes::{homogeneous::Homogeneous, grid::Grid},
+    materials::{diffuse::Diffuse, metal::Metal, emission::Emission, dielectric::{Dielectric, Ior}, boundary::Boundary, subsurface::Subsurface,
+        mix::{Mix, MixFactor}, coated::Coated,
+        shadow_catcher::ShadowCatcher, holdout::Holdout, cutout::Cutout}, textures::checker::Checker}};
+
+/** Built-in scenes that can be rendered, until scenes can be loaded from files */
+#[derive(Clone, Copy, PartialEq)]
+pub enum SceneType {
+    /** Yellow spheres on a blue ground under the sky, one of them animated */
+    Spheres,
+    /** Spheres lit by small emissive spheres in the dark, for the integrators sampling lights */
+    Lights,
+    /** A lamp inside a glass globe and other glass, lit only through glass, for the bidirectional
+     * path tracer and for dispersion in spectral mode */
+    Glass,
+    /** A smouldering cloud of smoke and a glowing ball of gas in fog, for participating media */
+    Volumes,
+    /** Spheres of wax, marble and skin scattering light under their surface, and blended and
+     * coated materials in front of them, under the sky */
+    Materials,
+    /** A ball, a holdout and a sphere cut into a checkerboard on a shadow catcher, for
+     * compositing onto photographs with a transparent background */
+    Catcher
+}
+
+impl SceneType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "spheres" => Some(SceneType::Spheres),
+            "lights" => Some(SceneType::Lights),
+            "glass" => Some(SceneType::Glass),
+            "volumes" => Some(SceneType::Volumes),
+            "materials" => Some(SceneType::Materials),
+            "catcher" => Some(SceneType::Catcher),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SceneType::Spheres => "spheres",
+            SceneType::Lights => "lights",
+            SceneType::Glass => "glass",
+            SceneType::Volumes => "volumes",
+            SceneType::Materials => "materials",
+            SceneType::Catcher => "catcher"
+        }
+    }
+
+    /** Aperture of the camera. Scenes lit through glass get a pinhole camera, which light subpaths
+     * of the bidirectional path tracer can be connected to. */
+    pub fn aperture(&self) -> f32 {
+        match self {
+            SceneType::Glass => 0.0,
+            _ => 0.1
+        }
+    }
+
+    /** Builds the scene, without a camera. The cloud of the volumes scene is loaded from the
+     * voxel file if one is given. */
+    pub fn build(&self, voxels: Option<&str>) -> Result<Scene, String> {
+        return match self {
+            SceneType::Spheres => Ok(spheres()),
+            SceneType::Lights => Ok(lights()),
+            SceneType::Glass => Ok(glass()),
+            SceneType::Materials => Ok(materials()),
+            SceneType::Catcher => Ok(catcher()),
+            SceneType::Volumes => {
+                let (min, max) = (Vector3::new(-1.9, -0.1, -1.5), Vector3::new(-0.9, 0.9, -0.5));
+                let cloud = match voxels {
+                    Some(path) => Grid::load(path, min, max, 12.0, Color::new(0.6, 0.6, 0.6), 0.3)?,
+                    None => Grid::new([CLOUD_RESOLUTION; 3], cloud_density(), min, max, 12.0, Color::new(0.6, 0.6, 0.6), 0.3)?
+                };
+                Ok(volumes(cloud))
+            }
+        };
+    }
+}
+
+/** Sun position in degrees and turbidity of the daylight sky, see `PreethamSky` */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sky {
+    pub elevation: f32,
+    pub azimuth: f32,
+    pub turbidity: f32
+}
+
+/** Daylight sky at `start` on the first frame, moving linearly to `end` on the last frame if given,
+ * for time-of-day sequences */
+pub fn sky(start: Sky, end: Option<Sky>, first_frame: u64, last_frame: u64) -> PreethamSky {
+    let mut sky = PreethamSky::new(start.elevation, start.azimuth, start.turbidity);
+
+    if let Some(end) = end {
+        let channels = [
+            ("sun_elevation", start.elevation, end.elevation),
+            ("sun_azimuth", start.azimuth, end.azimuth),
+            ("turbidity", start.turbidity, end.turbidity)
+        ];
+        for (name, first, last) in channels {
+            let mut channel = AnimationChannel::new();
+            channel.add_key(AnimationKey::new(first_frame as f32, first));
+            channel.add_key(AnimationKey::new(last_frame as f32, last));
+            sky.add_animation_channel(name.to_string(), channel);
+        }
+    }
+
+    return sky;
+}
+
+/* Adds a named sphere to the scene, returning its node */
+fn add_sphere(scene: &mut Scene, name: &str, center: Vector3, radius: f32, material: MaterialId) -> Arc<RwLock<Node>> {
+    let mut node = Node::new();
+    node.set_name(name);
+    node.set_renderable(Box::new(Sphere::new(center, radius, material)));
+
+    let node = Arc::new(RwLock::new(node));
+    scene.add_child(node.clone());
+    return node;
+}
+
+fn spheres() -> Scene {
+    let mut scene = Scene::new();
+
+    let materials = scene.materials_mut();
+    let yellow = materials.add("yellow", Arc::new(Diffuse::new(Color::new(0.8, 0.8, 0.0))));
+    let ground = materials.add("ground", Arc::new(Diffuse::new(Color::new(0.1, 0.2, 0.5))));
+
+    let mut node1 = Node::new();
+    let sphere1 = Sphere::new(
+        Vector3::new(0.0, 0.0, -1.0), 0.5,
+        yellow
+    );
+    let mut node2 = Node::new();
+    let sphere2 = Sphere::new(
+        Vector3::new(0.0, -100.5, -1.0), 100.0,
+        ground
+    );
+    let mut node3 = Node::new();
+    let sphere3 = Sphere::new(
+        Vector3::new(-1.0, 0.0, -1.0), 0.5,
+        yellow
+    );
+    let mut node4 = Node::new();
+    let sphere4 = Sphere::new(
+        Vector3::new(1.0, 0.0, -1.0), 0.5,
+        yellow
+    );
+
+    node1.set_renderable(Box::new(sphere1));
+    node2.set_renderable(Box::new(sphere2));
+    node3.set_renderable(Box::new(sphere3));
+    node4.set_renderable(Box::new(sphere4));
+
+    let mut animation_pos_y = AnimationChannel::new();
+    let mut anim_key1 = AnimationKey::new(1.0, 0.0);
+    let mut anim_key2 = AnimationKey::new(12.0, 1.0);
+    let mut anim_key3 = AnimationKey::new(23.0, 0.0);
+    anim_key1.change_interpolation(Interpolation::Bezier(
+        vec![Vector2::new(0.0, 0.0), Vector2::new(3.0, 0.75)]
+    ));
+    anim_key3.change_interpolation(Interpolation::Bezier(
+        vec![Vector2::new(21.0, 0.75), Vector2::new(23.0, 0.0)]
+    ));
+    anim_key2.change_interpolation(Interpolation::Bezier(
+        vec![Vector2::new(6.0, 1.0), Vector2::new(18.0, 1.0)]
+    ));
+    animation_pos_y.add_key(anim_key1);
+    animation_pos_y.add_key(anim_key2);
+    animation_pos_y.add_key(anim_key3);
+
+    node3.set_animation_channel("translation_y".to_string(), animation_pos_y);
+
+    let node1_arc = Arc::new(RwLock::new(node1));
+    let node2_arc = Arc::new(RwLock::new(node2));
+    let node3_arc = Arc::new(RwLock::new(node3));
+    let node4_arc = Arc::new(RwLock::new(node4));
+
+    Node::add_child(node3_arc.clone(), node4_arc);
+    scene.add_child(node1_arc);
+    scene.add_child(node2_arc);
+    scene.add_child(node3_arc);
+
+    return scene;
+}
+
+fn lights() -> Scene {
+    let mut scene = Scene::new();
+    scene.set_environment(Box::new(Gradient::new(
+        Color::new(0.01, 0.01, 0.015),
+        Color::new(0.01, 0.01, 0.015)
+    )));
+
+    let materials = scene.materials_mut();
+    let ground = materials.add("ground", Arc::new(Diffuse::new(Color::new(0.5, 0.5, 0.5))));
+    let red = materials.add("red", Arc::new(Diffuse::new(Color::new(0.7, 0.1, 0.1))));
+    let chrome = materials.add("chrome", Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.05)));
+    let blue = materials.add("blue", Arc::new(Diffuse::new(Color::new(0.1, 0.2, 0.6))));
+    let lamp = materials.add("lamp", Arc::new(Emission::new(Color::new(1.0, 0.9, 0.8), 12.0)));
+    let candle = materials.add("candle", Arc::new(Emission::new(Color::new(1.0, 0.5, 0.2), 40.0)));
+
+    add_sphere(&mut scene, "ground", Vector3::new(0.0, -100.5, -1.0), 100.0, ground);
+    add_sphere(&mut scene, "left", Vector3::new(-1.0, 0.0, -1.0), 0.5, red);
+    add_sphere(&mut scene, "middle", Vector3::new(0.0, 0.0, -1.0), 0.5, chrome);
+    add_sphere(&mut scene, "right", Vector3::new(1.0, 0.0, -1.0), 0.5, blue);
+    add_sphere(&mut scene, "lamp", Vector3::new(0.0, 1.5, -1.0), 0.25, lamp);
+    add_sphere(&mut scene, "candle", Vector3::new(1.2, -0.35, 0.2), 0.1, candle);
+
+    return scene;
+}
+
+fn glass() -> Scene {
+    let mut scene = Scene::new();
+    scene.set_environment(Box::new(Gradient::new(
+        Color::new(0.005, 0.005, 0.008),
+        Color::new(0.005, 0.005, 0.008)
+    )));
+
+    let mut frosted = Dielectric::new(1.5);
+    frosted.set_roughness(0.3);
+    let mut tinted = Dielectric::new(1.5);
+    tinted.set_absorption(Color::new(0.2, 0.8, 0.4), 1.0);
+
+    let materials = scene.materials_mut();
+    let ground = materials.add("ground", Arc::new(Diffuse::new(Color::new(0.5, 0.5, 0.5))));
+    // Borosilicate crown glass (BK7) and dense flint glass, splitting light into colors in spectral mode
+    let globe = materials.add("globe", Arc::new(Dielectric::with_ior(Ior::Sellmeier(
+        [1.039_612, 0.231_792_34, 1.010_469_5], [0.006_000_699, 0.020_017_914, 103.560_65]))));
+    let flint = materials.add("flint", Arc::new(Dielectric::with_ior(Ior::Cauchy(1.728, 0.013_42))));
+    let filament = materials.add("filament", Arc::new(Emission::new(Color::new(1.0, 0.8, 0.5), 60.0)));
+    let frosted = materials.add("frosted", Arc::new(frosted));
+    let tinted = materials.add("tinted", Arc::new(tinted));
+
+    add_sphere(&mut scene, "ground", Vector3::new(0.0, -100.5, -1.0), 100.0, ground);
+    add_sphere(&mut scene, "globe", Vector3::new(0.0, 0.0, -1.0), 0.5, globe);
+    add_sphere(&mut scene, "filament", Vector3::new(0.0, 0.0, -1.0), 0.1, filament);
+    add_sphere(&mut scene, "frosted", Vector3::new(-1.0, 0.0, -1.0), 0.5, frosted);
+    add_sphere(&mut scene, "tinted", Vector3::new(1.0, 0.0, -1.0), 0.5, tinted);
+    add_sphere(&mut scene, "flint", Vector3::new(0.5, -0.3, -0.2), 0.2, flint);
+
+    return scene;
+}
+
+/* Voxels along each side of the built-in cloud */
+const CLOUD_RESOLUTION: usize = 24;
+
+/* Lumpy ball of smoke thinning out towards its edge */
+fn cloud_density() -> Vec<f32> {
+    let n = CLOUD_RESOLUTION;
+    let mut density = Vec::with_capacity(n * n * n);
+    for z in 0..n {
+        for y in 0..n {
+            for x in 0..n {
+                let p = Vector3::new(x as f32, y as f32, z as f32) * (1.0 / (n - 1) as f32) - Vector3::new(0.5, 0.5, 0.5);
+                let lumps = 0.15 * (11.0 * p.x).sin() * (13.0 * p.y).sin() * (7.0 * p.z).sin();
+                density.push((1.0 - 2.2 * p.length() + lumps).max(0.0));
+            }
+        }
+    }
+    return density;
+}
+
+fn volumes(mut cloud: Grid) -> Scene {
+    let mut scene = Scene::new();
+    scene.set_environment(Box::new(Gradient::new(
+        Color::new(0.0, 0.0, 0.0),
+        Color::new(0.0, 0.0, 0.0)
+    )));
+
+    let fog = scene.add_medium(Arc::new(Homogeneous::new(Color::new(0.01, 0.01, 0.01), Color::new(0.04, 0.04, 0.04), 0.6)));
+    scene.set_global_medium(Some(fog));
+
+    cloud.set_emission(Color::new(3.0, 0.8, 0.2));
+    let cloud = scene.add_medium(Arc::new(cloud));
+    let mut gas = Homogeneous::new(Color::new(1.5, 1.5, 1.5), Color::new(1.0, 1.0, 1.0), 0.0);
+    gas.set_emission(Color::new(0.4, 1.2, 2.5));
+    let gas = scene.add_medium(Arc::new(gas));
+
+    let materials = scene.materials_mut();
+    let ground = materials.add("ground", Arc::new(Diffuse::new(Color::new(0.5, 0.5, 0.5))));
+    let white = materials.add("white", Arc::new(Diffuse::new(Color::new(0.8, 0.8, 0.8))));
+    let boundary = materials.add("boundary", Arc::new(Boundary::new()));
+    let lamp = materials.add("lamp", Arc::new(Emission::new(Color::new(1.0, 0.9, 0.8), 6.0)));
+
+    add_sphere(&mut scene, "ground", Vector3::new(0.0, -100.5, -1.0), 100.0, ground);
+    add_sphere(&mut scene, "middle", Vector3::new(0.0, 0.0, -1.0), 0.5, white);
+    add_sphere(&mut scene, "lamp", Vector3::new(0.5, 2.5, -2.5), 0.6, lamp);
+    // The spheres around the cloud's box and the gas only mark where the media are
+    let cloud_node = add_sphere(&mut scene, "cloud", Vector3::new(-1.4, 0.4, -1.0), 0.87, boundary);
+    cloud_node.write().unwrap().set_interior_medium(cloud);
+    let gas_node = add_sphere(&mut scene, "gas", Vector3::new(1.0, 0.0, -1.0), 0.5, boundary);
+    gas_node.write().unwrap().set_interior_medium(gas);
+
+    return scene;
+}
+
+fn materials() -> Scene {
+    let mut scene = Scene::new();
+
+    let materials = scene.materials_mut();
+    let ground = materials.add("ground", Arc::new(Diffuse::new(Color::new(0.5, 0.5, 0.5))));
+    let wax = materials.add("wax", Arc::new(Subsurface::new(Color::new(0.9, 0.75, 0.4), Color::new(0.5, 0.4, 0.3), 1.4)));
+    let marble = materials.add("marble", Arc::new(Subsurface::new(Color::new(0.9, 0.9, 0.88), Color::new(0.3, 0.3, 0.25), 1.5)));
+    let skin = materials.add("skin", Arc::new(Subsurface::new(Color::new(0.85, 0.55, 0.45), Color::new(0.35, 0.25, 0.18), 1.4)));
+
+    let blue: Arc<dyn Material> = Arc::new(Diffuse::new(Color::new(0.1, 0.2, 0.6)));
+    let white: Arc<dyn Material> = Arc::new(Diffuse::new(Color::new(0.8, 0.8, 0.8)));
+    let checker = Checker::new(Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0), 8.0);
+    let checkered = materials.add("checkered", Arc::new(Mix::new(blue, white, MixFactor::Texture(Arc::new(checker)))));
+    let mut varnished = Coated::new(Arc::new(Diffuse::new(Color::new(0.6, 0.1, 0.1))), 1.5);
+    varnished.set_roughness(0.05);
+    varnished.set_tint(Color::new(0.9, 0.7, 0.4), 0.1);
+    let varnished = materials.add("varnished", Arc::new(varnished));
+    let gold: Arc<dyn Material> = Arc::new(Metal::new(Color::new(0.9, 0.7, 0.3), 0.1));
+    let dust: Arc<dyn Material> = Arc::new(Diffuse::new(Color::new(0.5, 0.45, 0.4)));
+    let dusty = materials.add("dusty", Arc::new(Mix::new(gold, dust, MixFactor::Constant(0.3))));
+
+    add_sphere(&mut scene, "ground", Vector3::new(0.0, -100.5, -1.0), 100.0, ground);
+    add_sphere(&mut scene, "wax", Vector3::new(-1.0, 0.0, -1.0), 0.5, wax);
+    add_sphere(&mut scene, "marble", Vector3::new(0.0, 0.0, -1.0), 0.5, marble);
+    add_sphere(&mut scene, "skin", Vector3::new(1.0, 0.0, -1.0), 0.5, skin);
+    add_sphere(&mut scene, "checkered", Vector3::new(-1.2, -0.25, -0.1), 0.25, checkered);
+    add_sphere(&mut scene, "varnished", Vector3::new(-0.5, -0.25, 0.1), 0.25, varnished);
+    add_sphere(&mut scene, "dusty", Vector3::new(0.2, -0.25, 0.3), 0.25, dusty);
+
+    return scene;
+}
+
+fn catcher() -> Scene {
+    let mut scene = Scene::new();
+
+    let materials = scene.materials_mut();
+    let ground = materials.add("ground", Arc::new(ShadowCatcher::new(Color::new(0.5, 0.5, 0.5))));
+    let red = materials.add("red", Arc::new(Diffuse::new(Color::new(0.7, 0.1, 0.1))));
+    let hole = materials.add("hole", Arc::new(Holdout::new()));
+    let mask = Checker::new(Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0), 12.0);
+    let leaves = materials.add("leaves", Arc::new(Cutout::new(Arc::new(Diffuse::new(Color::new(0.2, 0.6, 0.1))), Arc::new(mask))));
+
+    add_sphere(&mut scene, "ground", Vector3::new(0.0, -100.5, -1.0), 100.0, ground);
+    add_sphere(&mut scene, "hole", Vector3::new(-1.0, 0.0, -1.0), 0.5, hole);
+    add_sphere(&mut scene, "ball", Vector3::new(0.0, 0.0, -1.0), 0.5, red);
+    add_sphere(&mut scene, "leaves", Vector3::new(1.0, 0.0, -1.0), 0.5, leaves);
+
+    return scene;
+}
@@ -0,0 +1,126 @@
+use std::sync::OnceLock;
+
+use crate::color::Color;
+
+/** Visible range used for wavelength sampling, in nanometers */
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 720.0;
+
+/* Basis spectra for RGB upsampling, from Smits, "An RGB to Spectrum Conversion for Reflectances".
+ * Ten equally sized bins between LAMBDA_MIN and LAMBDA_MAX. */
+const SMITS_WHITE: [f32; 10]   = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN: [f32; 10]    = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA: [f32; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW: [f32; 10]  = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f32; 10]     = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f32; 10]   = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE: [f32; 10]    = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+static WHITE_BALANCE: OnceLock<[f32; 3]> = OnceLock::new();
+
+/** Picks a wavelength uniformly from the visible range */
+pub fn sample_wavelength() -> f32 {
+    let u = crate::random::random();
+    LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+/** Value of the reflectance spectrum of an RGB color at the given wavelength */
+pub fn rgb_to_spectral(c: &Color, wavelength: f32) -> f32 {
+    let bin = ((wavelength - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN) * 10.0) as i32;
+    let i = bin.clamp(0, 9) as usize;
+
+    let (r, g, b) = (c.r, c.g, c.b);
+    if r <= g && r <= b {
+        let base = r * SMITS_WHITE[i];
+        if g <= b {
+            base + (g - r) * SMITS_CYAN[i] + (b - g) * SMITS_BLUE[i]
+        } else {
+            base + (b - r) * SMITS_CYAN[i] + (g - b) * SMITS_GREEN[i]
+        }
+    } else if g <= r && g <= b {
+        let base = g * SMITS_WHITE[i];
+        if r <= b {
+            base + (r - g) * SMITS_MAGENTA[i] + (b - r) * SMITS_BLUE[i]
+        } else {
+            base + (b - g) * SMITS_MAGENTA[i] + (r - b) * SMITS_RED[i]
+        }
+    } else {
+        let base = b * SMITS_WHITE[i];
+        if r <= g {
+            base + (r - b) * SMITS_YELLOW[i] + (g - r) * SMITS_GREEN[i]
+        } else {
+            base + (g - b) * SMITS_YELLOW[i] + (r - g) * SMITS_RED[i]
+        }
+    }
+}
+
+/** Gray color holding the spectral value of c, so it can travel through RGB arithmetic */
+pub fn monochromatic(c: &Color, wavelength: f32) -> Color {
+    let v = rgb_to_spectral(c, wavelength);
+    Color::new(v, v, v)
+}
+
+/* Piecewise Gaussian used by the CIE fit */
+fn lobe(x: f32, mu: f32, sigma1: f32, sigma2: f32) -> f32 {
+    let t = if x < mu {
+        (x - mu) / sigma1
+    } else {
+        (x - mu) / sigma2
+    };
+    (-0.5 * t * t).exp()
+}
+
+/** CIE 1931 color matching functions, using the multi-lobe fit from
+ * Wyman et al., "Simple Analytic Approximations to the CIE XYZ Color Matching Functions" */
+pub fn cie_xyz(wavelength: f32) -> (f32, f32, f32) {
+    let l = wavelength;
+    let x = 1.056 * lobe(l, 599.8, 37.9, 31.0)
+          + 0.362 * lobe(l, 442.0, 16.0, 26.7)
+          - 0.065 * lobe(l, 501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(l, 568.8, 46.9, 40.5)
+          + 0.286 * lobe(l, 530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(l, 437.0, 11.8, 36.0)
+          + 0.681 * lobe(l, 459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+/** Converts CIE XYZ to linear sRGB (D65) */
+pub fn xyz_to_rgb(x: f32, y: f32, z: f32) -> Color {
+    Color::new(
+         3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.969266 * x + 1.8760108 * y + 0.0415560 * z,
+         0.0556434 * x - 0.2040259 * y + 1.0572252 * z
+    )
+}
+
+/* RGB response to a constant spectrum of 1, integrated over the sampled range */
+fn white_balance() -> &'static [f32; 3] {
+    WHITE_BALANCE.get_or_init(|| {
+        let mut sum = [0.0; 3];
+        let mut l = LAMBDA_MIN;
+        while l <= LAMBDA_MAX {
+            let (x, y, z) = cie_xyz(l);
+            let c = xyz_to_rgb(x, y, z);
+            sum[0] += c.r;
+            sum[1] += c.g;
+            sum[2] += c.b;
+            l += 1.0;
+        }
+        sum
+    })
+}
+
+/** Turns a radiance sample carried at one uniformly sampled wavelength into RGB.
+ * The result is normalized so that a flat spectrum averages to white. */
+pub fn spectral_to_rgb(value: f32, wavelength: f32) -> Color {
+    let (x, y, z) = cie_xyz(wavelength);
+    let c = xyz_to_rgb(x, y, z);
+    let w = white_balance();
+    let range = LAMBDA_MAX - LAMBDA_MIN;
+
+    Color::new(
+        value * c.r * range / w[0],
+        value * c.g * range / w[1],
+        value * c.b * range / w[2]
+    )
+}
@@ -0,0 +1,135 @@
+use std::{sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}}, time::Duration};
+
+/* Counters of one thread. Only the owning thread writes them, so increments don't contend,
+ * and they stay registered after the thread ends so that its work is still reported. */
+#[derive(Default)]
+struct Counters {
+    rays: AtomicU64,
+    intersection_tests: AtomicU64,
+    camera_samples: AtomicU64,
+    large_steps: AtomicU64,
+    large_accepted: AtomicU64,
+    small_steps: AtomicU64,
+    small_accepted: AtomicU64
+}
+
+static COUNTERS: Mutex<Vec<Arc<Counters>>> = Mutex::new(Vec::new());
+static PHASE_NANOS: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+
+thread_local! {
+    static THREAD_COUNTERS: Arc<Counters> = {
+        let counters = Arc::new(Counters::default());
+        COUNTERS.lock().unwrap().push(counters.clone());
+        counters
+    };
+}
+
+/** Parts of rendering a frame whose time is reported separately */
+#[derive(Clone, Copy)]
+pub enum Phase {
+    /** Animating the scene and preparing the integrator, e.g. shooting photons */
+    Setup,
+    /** Taking the samples of the pixels */
+    Rendering,
+    /** Denoising and writing the image, passes and checkpoints */
+    Output
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Setup => "setup",
+            Phase::Rendering => "rendering",
+            Phase::Output => "output"
+        }
+    }
+}
+
+/** Totals of the counters over all threads */
+pub struct Totals {
+    pub rays: u64,
+    pub intersection_tests: u64,
+    pub camera_samples: u64,
+    /** Large step and small step mutations of Metropolis light transport, and how many were accepted */
+    pub large_steps: u64,
+    pub large_accepted: u64,
+    pub small_steps: u64,
+    pub small_accepted: u64
+}
+
+fn increment(counter: &AtomicU64) {
+    counter.store(counter.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+}
+
+/** Counts a ray traced through the scene */
+pub fn add_ray() {
+    THREAD_COUNTERS.with(|c| increment(&c.rays));
+}
+
+/** Counts a ray tested against a single object */
+pub fn add_intersection_test() {
+    THREAD_COUNTERS.with(|c| increment(&c.intersection_tests));
+}
+
+/** Counts a sample taken through the camera */
+pub fn add_camera_sample() {
+    THREAD_COUNTERS.with(|c| increment(&c.camera_samples));
+}
+
+/** Counts a mutation of a Metropolis light transport chain */
+pub fn add_mutation(large_step: bool, accepted: bool) {
+    THREAD_COUNTERS.with(|c| {
+        let (steps, accepted_steps) = if large_step {
+            (&c.large_steps, &c.large_accepted)
+        } else {
+            (&c.small_steps, &c.small_accepted)
+        };
+        increment(steps);
+        if accepted {
+            increment(accepted_steps);
+        }
+    });
+}
+
+/** Adds time spent in a phase */
+pub fn add_time(phase: Phase, time: Duration) {
+    PHASE_NANOS[phase as usize].fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+}
+
+pub fn totals() -> Totals {
+    let counters = COUNTERS.lock().unwrap();
+    let sum = |f: fn(&Counters) -> &AtomicU64| counters.iter().map(|c| f(c).load(Ordering::Relaxed)).sum();
+    return Totals {
+        rays: sum(|c| &c.rays),
+        intersection_tests: sum(|c| &c.intersection_tests),
+        camera_samples: sum(|c| &c.camera_samples),
+        large_steps: sum(|c| &c.large_steps),
+        large_accepted: sum(|c| &c.large_accepted),
+        small_steps: sum(|c| &c.small_steps),
+        small_accepted: sum(|c| &c.small_accepted)
+    };
+}
+
+/** Prints the statistics of everything rendered so far, taking the given time in total */
+pub fn report(total: Duration) {
+    let totals = totals();
+    let seconds = total.as_secs_f64().max(1e-9);
+    println!("Render statistics:");
+    println!("  Camera samples: {}", totals.camera_samples);
+    println!("  Rays cast: {} ({:.2} million per second)", totals.rays, totals.rays as f64 / seconds / 1e6);
+    if totals.camera_samples > 0 {
+        println!("  Average path length: {:.2} rays per camera sample",
+            totals.rays as f64 / totals.camera_samples as f64);
+    }
+    println!("  Intersection tests: {} ({:.1} per ray)", totals.intersection_tests,
+        totals.intersection_tests as f64 / totals.rays.max(1) as f64);
+    if totals.large_steps + totals.small_steps > 0 {
+        println!("  Metropolis mutations: {} large steps ({:.1}% accepted), {} small steps ({:.1}% accepted)",
+            totals.large_steps, 100.0 * totals.large_accepted as f64 / totals.large_steps.max(1) as f64,
+            totals.small_steps, 100.0 * totals.small_accepted as f64 / totals.small_steps.max(1) as f64);
+    }
+    for phase in [Phase::Setup, Phase::Rendering, Phase::Output] {
+        let time = Duration::from_nanos(PHASE_NANOS[phase as usize].load(Ordering::Relaxed));
+        println!("  Time spent in {}: {:.2}s", phase.name(), time.as_secs_f32());
+    }
+}
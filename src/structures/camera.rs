@@ -1,5 +1,4 @@
 use crate::{math::{vector3::Vector3, matrix3::Matrix3}, ray::Ray};
-use rand::prelude::*;
 
 pub struct CameraBuilder {
     camera: Camera
@@ -92,6 +91,45 @@ impl Camera {
         self.update_viewport_dim();
     }
 
+    pub fn location(&self) -> &Vector3 {
+        &self.location
+    }
+
+    pub fn aperture_size(&self) -> f32 {
+        self.aperture_size
+    }
+
+    /** Direction the camera is looking in */
+    pub fn forward(&self) -> Vector3 {
+        (&self.rotation_matrix * &Vector3::new(0.0, 0.0, -1.0)).normalize()
+    }
+
+    /** Pixel a point is seen in through the center of the lens, None if it is out of view */
+    pub fn project(&self, p: &Vector3, pixel_width: u64, pixel_height: u64) -> Option<(u64, u64)> {
+        let local = &self.rotation_matrix.transpose() * &(p - &self.location);
+        if local.z >= 0.0 {
+            return None;
+        }
+
+        let x = local.x / -local.z;
+        let y = local.y / -local.z;
+        let i = pixel_width as f32 * (x / self.width + 0.5);
+        let j = pixel_height as f32 * (0.5 - y / self.height);
+        if i < 0.0 || j < 0.0 || i >= pixel_width as f32 || j >= pixel_height as f32 {
+            return None;
+        }
+        return Some((i as u64, j as u64));
+    }
+
+    /** Solid angle pdf of get_ray choosing the direction, for a camera without aperture */
+    pub fn pdf_direction(&self, dir: &Vector3) -> f32 {
+        let cos = &dir.normalize() * &self.forward();
+        if cos <= 0.0 {
+            return 0.0;
+        }
+        return 1.0 / (self.width * self.height * cos * cos * cos);
+    }
+
     pub fn update_viewport_dim(&mut self) {
         let theta = self.vfov.to_radians();
         let height = (theta / 2.0).tan() * 2.0;
@@ -106,18 +144,21 @@ impl Camera {
     }
 
     pub fn get_ray(&self, i: u64, j: u64, pixel_width: u64, pixel_height: u64) -> Ray {
-        let rand1: f32 = rand::thread_rng().gen();
-        let rand2: f32 = rand::thread_rng().gen();
-        
+        let rand1 = crate::random::random();
+        let rand2 = crate::random::random();
+
+        return self.get_ray_at(i as f32 + rand1, j as f32 + rand2, pixel_width, pixel_height);
+    }
+
+    /** Ray through the given raster position, where pixel (i, j) spans from i to i + 1 */
+    pub fn get_ray_at(&self, i: f32, j: f32, pixel_width: u64, pixel_height: u64) -> Ray {
         let w = self.width;
         let h = self.height;
         let v = pixel_width as f32;
         let c = pixel_height as f32;
-        let i = i as f32;
-        let j = j as f32;
 
-        let x = (w * (-v + rand1 + 2.0*i)) / (2.0 * v);
-        let y = (h * (c - rand2 - 2.0*j)) / (2.0 * c);
+        let x = (w * (-v + 2.0*i)) / (2.0 * v);
+        let y = (h * (c - 2.0*j)) / (2.0 * c);
         let z = -1.0;
 
         let dir = Vector3::new(x, y, z);
@@ -126,9 +167,9 @@ impl Camera {
 
         let focal_point = &self.location + &(&rotated_dir * self.focus_dist);
         
-        let x: f32 = (rand::random::<f32>() - 0.5) * self.aperture_size + self.location.x;
-        let y: f32 = (rand::random::<f32>() - 0.5) * self.aperture_size + self.location.y;
-        let z: f32 = (rand::random::<f32>() - 0.5) * self.aperture_size + self.location.z;
+        let x: f32 = (crate::random::random() - 0.5) * self.aperture_size + self.location.x;
+        let y: f32 = (crate::random::random() - 0.5) * self.aperture_size + self.location.y;
+        let z: f32 = (crate::random::random() - 0.5) * self.aperture_size + self.location.z;
         let or = Vector3::new(x, y, z);
 
         let new_dir = (&focal_point - &or).normalize();
@@ -0,0 +1,21 @@
+use std::f32::consts::PI;
+
+use crate::{color::Color, math::vector3::Vector3};
+
+/** Light arriving from infinitely far away, seen by rays that leave the scene */
+pub trait Environment: Send + Sync {
+    fn color(&self, dir: &Vector3) -> Color;
+
+    /** Samples a direction towards the environment, returned with its solid angle pdf */
+    fn sample(&self) -> (Vector3, f32) {
+        (Vector3::random_in_unit_sphere(), 1.0 / (4.0 * PI))
+    }
+
+    /** Solid angle pdf of `sample` returning the direction */
+    fn pdf(&self, _dir: &Vector3) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+
+    /** Prepares the environment for the given frame, e.g. moves the sun */
+    fn update(&mut self, _f: f32) {}
+}
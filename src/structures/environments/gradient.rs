@@ -0,0 +1,27 @@
+use crate::{color::Color, math::vector3::Vector3, structures::environment::Environment};
+
+/** Vertical blend between a horizon and a zenith color */
+pub struct Gradient {
+    horizon: Color,
+    zenith: Color
+}
+
+impl Gradient {
+    pub fn new(horizon: Color, zenith: Color) -> Self {
+        Gradient {
+            horizon, zenith
+        }
+    }
+}
+
+impl Environment for Gradient {
+    fn color(&self, dir: &Vector3) -> Color {
+        let d = dir.normalize();
+        let t = 0.5 * (d.y + 1.0);
+
+        let c1 = Color::scale(&self.horizon, 1.0 - t);
+        let c2 = Color::scale(&self.zenith, t);
+
+        return Color::add(&c1, &c2);
+    }
+}
@@ -0,0 +1,197 @@
+use std::{collections::HashMap, f32::consts::PI};
+
+use crate::{animation::animation::AnimationChannel, color::Color, math::vector3::Vector3, spectrum, structures::environment::Environment};
+
+/* Perez distribution coefficients A-E as linear functions of turbidity, for Y, x and y */
+const PEREZ_Y: [(f32, f32); 5] = [(0.1787, -1.4630), (-0.3554, 0.4275), (-0.0227, 5.3251), (0.1206, -2.5771), (-0.0670, 0.3703)];
+const PEREZ_X: [(f32, f32); 5] = [(-0.0193, -0.2592), (-0.0665, 0.0008), (-0.0004, 0.2125), (-0.0641, -0.8989), (-0.0033, 0.0452)];
+const PEREZ_YC: [(f32, f32); 5] = [(-0.0167, -0.2608), (-0.0950, 0.0092), (-0.0079, 0.2102), (-0.0441, -1.6537), (-0.0109, 0.0529)];
+
+/** Analytic daylight sky after Preetham et al., "A Practical Analytic Model for Daylight",
+ * with a sun disk in the sky.
+ *
+ * The sun position and turbidity can be animated through the channels
+ * `sun_elevation`, `sun_azimuth` (both in degrees) and `turbidity`. */
+pub struct PreethamSky {
+    sun_elevation: f32,
+    sun_azimuth: f32,
+    turbidity: f32,
+    intensity: f32,
+    sun_strength: f32,
+    sun_size: f32,
+    ground_albedo: f32,
+    animation_channels: HashMap<String, AnimationChannel>,
+
+    sun_direction: Vector3,
+    sun_radiance: Color,
+    cos_sun_radius: f32,
+    zenith: [f32; 3],
+    perez: [[f32; 5]; 3],
+    perez_norm: [f32; 3]
+}
+
+impl PreethamSky {
+    /** Elevation and azimuth of the sun are in degrees; azimuth 0 is towards -z, 90 towards +x.
+     * Turbidity describes the haziness of the atmosphere, from 2 (clear) to 10 (hazy). */
+    pub fn new(sun_elevation: f32, sun_azimuth: f32, turbidity: f32) -> Self {
+        let mut sky = PreethamSky {
+            sun_elevation,
+            sun_azimuth,
+            turbidity,
+            intensity: 1.0 / 15.0,
+            sun_strength: 4.0,
+            sun_size: 0.53,
+            ground_albedo: 0.3,
+            animation_channels: HashMap::new(),
+            sun_direction: Vector3::new(0.0, 1.0, 0.0),
+            sun_radiance: Color::new(0.0, 0.0, 0.0),
+            cos_sun_radius: 1.0,
+            zenith: [0.0; 3],
+            perez: [[0.0; 5]; 3],
+            perez_norm: [1.0; 3]
+        };
+        sky.precompute(sun_elevation, sun_azimuth, turbidity);
+        return sky;
+    }
+
+    pub fn add_animation_channel(&mut self, name: String, ch: AnimationChannel) {
+        self.animation_channels.insert(name, ch);
+    }
+
+    fn value_by_frame(&self, name: &str, default: f32, f: f32) -> f32 {
+        match self.animation_channels.get(name) {
+            Some(ch) => ch.get_value_at_frame(f, default),
+            None => default
+        }
+    }
+
+    fn perez(c: &[f32; 5], theta: f32, gamma: f32) -> f32 {
+        let cos_theta = theta.cos().max(0.01);
+        let cos_gamma = gamma.cos();
+        (1.0 + c[0] * (c[1] / cos_theta).exp()) * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+    }
+
+    fn precompute(&mut self, elevation: f32, azimuth: f32, turbidity: f32) {
+        let t = turbidity.max(1.7);
+        let el = elevation.to_radians();
+        let az = azimuth.to_radians();
+        self.sun_direction = Vector3::new(el.cos() * az.sin(), el.sin(), -el.cos() * az.cos());
+
+        // The model is only defined for the sun above the horizon
+        let theta_s = (PI / 2.0 - el).clamp(0.0, PI / 2.0 - 0.01);
+        let ts = theta_s;
+        let ts2 = ts * ts;
+        let ts3 = ts2 * ts;
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * ts);
+        let y_z = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let x_z = t * t * (0.00166 * ts3 - 0.00375 * ts2 + 0.00209 * ts)
+                + t * (-0.02903 * ts3 + 0.06377 * ts2 - 0.03202 * ts + 0.00394)
+                + (0.11693 * ts3 - 0.21196 * ts2 + 0.06052 * ts + 0.25886);
+        let yc_z = t * t * (0.00275 * ts3 - 0.00610 * ts2 + 0.00317 * ts)
+                + t * (-0.04214 * ts3 + 0.08970 * ts2 - 0.04153 * ts + 0.00516)
+                + (0.15346 * ts3 - 0.26756 * ts2 + 0.06670 * ts + 0.26688);
+        self.zenith = [y_z * self.intensity, x_z, yc_z];
+
+        let tables = [PEREZ_Y, PEREZ_X, PEREZ_YC];
+        for (i, table) in tables.iter().enumerate() {
+            for (coefficient, (a, b)) in self.perez[i].iter_mut().zip(table) {
+                *coefficient = a * t + b;
+            }
+            self.perez_norm[i] = PreethamSky::perez(&self.perez[i], 0.0, theta_s);
+        }
+
+        // Sun color from atmospheric extinction along the relative air mass (Kasten and Young)
+        let theta_deg = theta_s.to_degrees();
+        let air_mass = 1.0 / (theta_s.cos() + 0.50572 * (96.07995 - theta_deg).powf(-1.6364));
+        let rayleigh = [0.0058, 0.0135, 0.0331];
+        let haze = 0.008 * t;
+        let tr = rayleigh.map(|r| (-air_mass * (r * 8.0 + haze)).exp());
+
+        let radius = (self.sun_size / 2.0).to_radians();
+        self.cos_sun_radius = radius.cos();
+        let solid_angle = 2.0 * PI * (1.0 - self.cos_sun_radius);
+        let horizon_fade = if elevation > 0.0 { 1.0 } else { 0.0 };
+        let radiance = self.sun_strength / solid_angle * horizon_fade;
+        self.sun_radiance = Color::new(tr[0] * radiance, tr[1] * radiance, tr[2] * radiance);
+    }
+
+    fn sky(&self, theta: f32, gamma: f32) -> Color {
+        let mut v = [0.0; 3];
+        for (i, value) in v.iter_mut().enumerate() {
+            *value = self.zenith[i] * PreethamSky::perez(&self.perez[i], theta, gamma) / self.perez_norm[i];
+        }
+        let (luminance, x, y) = (v[0], v[1], v[2]);
+        if y <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+        let mut c = spectrum::xyz_to_rgb(big_x, luminance, big_z);
+        c.r = c.r.max(0.0);
+        c.g = c.g.max(0.0);
+        c.b = c.b.max(0.0);
+        return c;
+    }
+}
+
+impl Environment for PreethamSky {
+    fn color(&self, dir: &Vector3) -> Color {
+        let d = dir.normalize();
+        let cos_gamma = (&d * &self.sun_direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+
+        if d.y <= 0.0 {
+            let horizon = self.sky(PI / 2.0 - 0.001, gamma);
+            return Color::scale(&horizon, self.ground_albedo);
+        }
+
+        let theta = d.y.min(1.0).acos();
+        let sky = self.sky(theta, gamma);
+        if cos_gamma >= self.cos_sun_radius {
+            return sky + self.sun_radiance.copy();
+        }
+        return sky;
+    }
+
+    /* Half of the samples go towards the sun disk, the rest is spread over the whole sphere */
+    fn sample(&self) -> (Vector3, f32) {
+        if self.sun_radiance.luminance() > 0.0 && crate::random::random() < 0.5 {
+            let cos_theta = 1.0 - crate::random::random() * (1.0 - self.cos_sun_radius);
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = 2.0 * PI * crate::random::random();
+
+            let (t, s) = Vector3::orthonormal_basis(&self.sun_direction);
+            let dir = t * (sin_theta * phi.cos()) + s * (sin_theta * phi.sin()) + &self.sun_direction * cos_theta;
+            let pdf = self.pdf(&dir);
+            return (dir, pdf);
+        }
+
+        let dir = Vector3::random_in_unit_sphere();
+        let pdf = self.pdf(&dir);
+        return (dir, pdf);
+    }
+
+    fn pdf(&self, dir: &Vector3) -> f32 {
+        let uniform = 1.0 / (4.0 * PI);
+        if self.sun_radiance.luminance() <= 0.0 {
+            return uniform;
+        }
+
+        let cos_gamma = &dir.normalize() * &self.sun_direction;
+        let cone = if cos_gamma >= self.cos_sun_radius {
+            1.0 / (2.0 * PI * (1.0 - self.cos_sun_radius))
+        } else {
+            0.0
+        };
+        return 0.5 * cone + 0.5 * uniform;
+    }
+
+    fn update(&mut self, f: f32) {
+        let elevation = self.value_by_frame("sun_elevation", self.sun_elevation, f);
+        let azimuth = self.value_by_frame("sun_azimuth", self.sun_azimuth, f);
+        let turbidity = self.value_by_frame("turbidity", self.turbidity, f);
+        self.precompute(elevation, azimuth, turbidity);
+    }
+}
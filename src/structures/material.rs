@@ -1,13 +1,62 @@
-use crate::{ray::Ray, color::Color};
+use crate::{ray::Ray, color::Color, math::vector3::Vector3};
 
 use super::renderable::HitRecord;
 
+/** Kind of scattering event, used to limit the number of bounces of each kind */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Lobe {
+    Diffuse,
+    Glossy,
+    Transmission,
+    Volume
+}
+
 pub struct ScatterResult {
     pub ray: Ray,
-    pub attenuation: Color
+    pub attenuation: Color,
+    pub lobe: Lobe,
+    /** Index of refraction on the side the ray came from over that of the side it went to,
+     * 1 unless it was refracted */
+    pub eta: f32
 }
 
-pub trait Material {
+pub trait Material: Send + Sync {
     fn scatter(&self, r_in: &Ray, hit: &HitRecord) -> Option<ScatterResult>;
-    fn copy(&self) -> Box<dyn Material>;
-}
\ No newline at end of file
+
+    /** BSDF times the cosine term for light arriving from `wi` and leaving towards `wo`
+     * (both normalized, pointing away from the surface), together with the pdf of `scatter`
+     * picking `wi`. Materials that can only be sampled, like mirrors and glass, return None. */
+    fn eval(&self, _wo: &Vector3, _wi: &Vector3, _hit: &HitRecord) -> Option<(Color, f32)> {
+        None
+    }
+
+    /** Overall reflectance of the surface, written to the albedo render pass */
+    fn albedo(&self, _hit: &HitRecord) -> Color {
+        Color::new(1.0, 1.0, 1.0)
+    }
+
+    /** Light emitted by the surface at the hit point */
+    fn emitted(&self, _hit: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    /** Objects with emissive materials are sampled as lights */
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /** Opacity of the surface at the hit point; rays pass straight through the rest */
+    fn alpha(&self, _hit: &HitRecord) -> f32 {
+        1.0
+    }
+
+    /** Holdouts punch a transparent hole into the image where the camera sees them */
+    fn is_holdout(&self) -> bool {
+        false
+    }
+
+    /** Shadow catchers are transparent to the camera except for the shadows they receive */
+    fn is_shadow_catcher(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,54 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::material::Material;
+
+/** Handle of a material stored in a MaterialLibrary */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MaterialId(usize);
+
+impl MaterialId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+/** Scene-wide storage of materials, shared by every renderable that references them */
+pub struct MaterialLibrary {
+    materials: Vec<Arc<dyn Material>>,
+    names: Vec<String>,
+    ids: HashMap<String, MaterialId>
+}
+
+impl MaterialLibrary {
+    pub fn new() -> Self {
+        MaterialLibrary {
+            materials: Vec::new(),
+            names: Vec::new(),
+            ids: HashMap::new()
+        }
+    }
+
+    /** Adds a material under the given name. If the name is taken, the material is
+     * replaced for every object using it and the existing handle is returned. */
+    pub fn add(&mut self, name: &str, material: Arc<dyn Material>) -> MaterialId {
+        if let Some(id) = self.ids.get(name) {
+            self.materials[id.0] = material;
+            return *id;
+        }
+
+        let id = MaterialId(self.materials.len());
+        self.materials.push(material);
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        return id;
+    }
+
+    pub fn get(&self, id: MaterialId) -> &Arc<dyn Material> {
+        &self.materials[id.0]
+    }
+
+    /** Names of all materials, in the order of their indices */
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+}
@@ -0,0 +1,20 @@
+use crate::{ray::Ray, structures::{material::{Material, ScatterResult}, renderable::HitRecord}};
+
+/** Invisible surface that only marks the extent of a node's interior medium */
+pub struct Boundary {}
+
+impl Boundary {
+    pub fn new() -> Self {
+        Boundary {}
+    }
+}
+
+impl Material for Boundary {
+    fn scatter(&self, _: &Ray, _: &HitRecord) -> Option<ScatterResult> {
+        None
+    }
+
+    fn alpha(&self, _: &HitRecord) -> f32 {
+        0.0
+    }
+}
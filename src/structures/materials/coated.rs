@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use crate::{color::Color, math::vector3::Vector3, ray::Ray, structures::{material::{Material, ScatterResult, Lobe}, renderable::HitRecord}};
+
+use super::dielectric::Dielectric;
+
+/** Clear dielectric coat (varnish, lacquer) over an arbitrary base material */
+pub struct Coated {
+    base: Arc<dyn Material>,
+    ior: f32,
+    roughness: f32,
+    tint: Color,
+    thickness: f32
+}
+
+impl Coated {
+    pub fn new(base: Arc<dyn Material>, ior: f32) -> Self {
+        Coated {
+            base,
+            ior,
+            roughness: 0.0,
+            tint: Color::new(1.0, 1.0, 1.0),
+            thickness: 0.0
+        }
+    }
+
+    /** Roughness of the coat, 0.0 means a perfectly smooth varnish. */
+    pub fn set_roughness(&mut self, roughness: f32) {
+        self.roughness = roughness.clamp(0.0, 1.0);
+    }
+
+    /** Color of the coat layer; absorption grows with the thickness and the path length through the coat */
+    pub fn set_tint(&mut self, tint: Color, thickness: f32) {
+        self.tint = tint;
+        self.thickness = thickness;
+    }
+
+    /* Absorption along straight paths through the coat, at the given angles to the normal */
+    fn coat_absorption(&self, cosines: &[f32]) -> Color {
+        if self.thickness <= 0.0 {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+
+        let path = self.thickness * cosines.iter().map(|c| 1.0 / c.max(1e-3)).sum::<f32>();
+        Color::new(
+            self.tint.r.max(0.0).powf(path),
+            self.tint.g.max(0.0).powf(path),
+            self.tint.b.max(0.0).powf(path)
+        )
+    }
+}
+
+impl Material for Coated {
+    fn scatter(&self, r_in: &Ray, hit: &HitRecord) -> Option<ScatterResult> {
+        if !hit.front_face() {
+            return self.base.scatter(r_in, hit);
+        }
+
+        let unit_dir = r_in.get_direction().normalize();
+        let n = hit.n();
+        let cos_in = (&-&unit_dir * n).clamp(0.0, 1.0);
+        let f_in = Dielectric::schlick_reflectance(cos_in, self.ior);
+        let rand_num = crate::random::random();
+
+        // Light is reflected by the coat with probability F, otherwise it
+        // reaches the base and has to pass through the coat again on exit.
+        if rand_num < f_in {
+            let reflected = Vector3::reflection(&unit_dir, n);
+            let dir = reflected + Vector3::random_in_unit_sphere() * self.roughness;
+            if &dir * n <= 0.0 {
+                return None;
+            }
+
+            let p_fixed = hit.p().copy() + n * 0.0001;
+            return Some(ScatterResult {
+                ray: Ray::new(p_fixed, dir),
+                attenuation: Color::new(1.0, 1.0, 1.0),
+                lobe: Lobe::Glossy,
+                eta: 1.0
+            });
+        }
+
+        // The base is lit by the light refracted into the coat, and what it sends back up is
+        // refracted again on the way out. Light reflected back down at the top of the coat
+        // is taken as absorbed instead of bouncing between the coat and the base.
+        let mut r_coat = Ray::new(hit.p().copy(), Vector3::refraction(&unit_dir, n, 1.0 / self.ior));
+        r_coat.set_wavelength(r_in.get_wavelength());
+        let cos_coat_in = (&-r_coat.get_direction() * n).clamp(0.0, 1.0);
+
+        let base = self.base.scatter(&r_coat, hit)?;
+        let base_dir = base.ray.get_direction().normalize();
+        let cos_coat_out = &base_dir * n;
+        if cos_coat_out <= 0.0 {
+            // Transmitted into the base, e.g. through coated glass
+            let absorption = self.coat_absorption(&[cos_coat_in]);
+            return Some(ScatterResult {
+                ray: base.ray,
+                attenuation: &base.attenuation * &absorption,
+                lobe: base.lobe,
+                eta: base.eta
+            });
+        }
+        if self.ior * self.ior * (1.0 - cos_coat_out * cos_coat_out) > 1.0 {
+            return None;
+        }
+
+        let out_dir = Vector3::refraction(&base_dir, &-n, self.ior);
+        let cos_out = (&out_dir * n).clamp(0.0, 1.0);
+        let f_out = Dielectric::schlick_reflectance(cos_out, self.ior);
+
+        let absorption = self.coat_absorption(&[cos_coat_in, cos_coat_out]);
+        let attenuation = Color::scale(&(&base.attenuation * &absorption), 1.0 - f_out);
+
+        Some(ScatterResult {
+            ray: Ray::new(base.ray.get_origin().copy(), out_dir),
+            attenuation,
+            lobe: base.lobe,
+            eta: base.eta
+        })
+    }
+
+    fn alpha(&self, hit: &HitRecord) -> f32 {
+        self.base.alpha(hit)
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Color {
+        self.base.albedo(hit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::vector2::Vector2, structures::{material_library::MaterialLibrary, materials::metal::Metal}};
+
+    #[test]
+    fn coated_mirror_reflects_like_a_mirror() {
+        let mirror = Arc::new(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0));
+        let coated = Coated::new(mirror.clone(), 1.5);
+        let id = MaterialLibrary::new().add("mirror", mirror);
+        let hit = HitRecord::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), 1.0, true, Vector2::new(0.0, 0.0), id);
+        let r_in = Ray::new(Vector3::new(-1.0, 1.0, 0.0), Vector3::new(1.0, -1.0, 0.0));
+
+        // Refracted into the coat and out again, the light leaves at the angle it came in
+        let expected = Vector3::new(1.0, 1.0, 0.0).normalize();
+        for _ in 0..100 {
+            let scatter = coated.scatter(&r_in, &hit).unwrap();
+            let dir = scatter.ray.get_direction().normalize();
+            assert!(Vector3::distance(&dir, &expected) < 1e-4);
+            assert!(scatter.attenuation.r <= 1.0);
+        }
+    }
+}
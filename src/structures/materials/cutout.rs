@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use crate::{color::Color, math::vector3::Vector3, ray::Ray, structures::{material::{Material, ScatterResult}, renderable::HitRecord, texture::Texture}};
+
+/** Wraps a material with an opacity mask, e.g. for leaves and decals.
+ * The luminance of the mask is used as alpha. */
+pub struct Cutout {
+    base: Arc<dyn Material>,
+    mask: Arc<dyn Texture>
+}
+
+impl Cutout {
+    pub fn new(base: Arc<dyn Material>, mask: Arc<dyn Texture>) -> Self {
+        Cutout {
+            base, mask
+        }
+    }
+}
+
+impl Material for Cutout {
+    fn scatter(&self, r_in: &Ray, hit: &HitRecord) -> Option<ScatterResult> {
+        self.base.scatter(r_in, hit)
+    }
+
+    fn eval(&self, wo: &Vector3, wi: &Vector3, hit: &HitRecord) -> Option<(Color, f32)> {
+        self.base.eval(wo, wi, hit)
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Color {
+        self.base.emitted(hit)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.base.is_emissive()
+    }
+
+    fn is_holdout(&self) -> bool {
+        self.base.is_holdout()
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.base.is_shadow_catcher()
+    }
+
+    fn alpha(&self, hit: &HitRecord) -> f32 {
+        self.mask.value(hit).luminance() * self.base.alpha(hit)
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Color {
+        self.base.albedo(hit)
+    }
+}
@@ -1,63 +1,217 @@
-use crate::{structures::{material::{Material, ScatterResult}, renderable::HitRecord}, color::Color, math::vector3::Vector3, ray::Ray};
+use std::f32::consts::PI;
+
+use crate::{structures::{material::{Material, ScatterResult, Lobe}, renderable::HitRecord}, color::Color, math::vector3::Vector3, ray::Ray};
+
+/** Index of refraction, optionally dependent on wavelength */
+pub enum Ior {
+    Constant(f32),
+    /** Cauchy's equation n = A + B / λ², with B in µm² */
+    Cauchy(f32, f32),
+    /** Sellmeier coefficients B1..B3 and C1..C3, with C in µm² */
+    Sellmeier([f32; 3], [f32; 3])
+}
+
+impl Ior {
+    /* Wavelength used when rendering in RGB mode (sodium D line) */
+    const REFERENCE_WAVELENGTH: f32 = 589.3;
+
+    pub fn at(&self, wavelength: Option<f32>) -> f32 {
+        let l = wavelength.unwrap_or(Ior::REFERENCE_WAVELENGTH) / 1000.0;
+        let l2 = l * l;
+        match self {
+            Ior::Constant(n) => *n,
+            Ior::Cauchy(a, b) => a + b / l2,
+            Ior::Sellmeier(b, c) => {
+                let mut n2 = 1.0;
+                for i in 0..3 {
+                    n2 += b[i] * l2 / (l2 - c[i]);
+                }
+                n2.max(1.0).sqrt()
+            }
+        }
+    }
+}
 
 pub struct Dielectric {
-    ior: f32
+    ior: Ior,
+    absorption: Color,
+    density: f32,
+    roughness: f32
 }
 
 impl Dielectric {
     pub fn new(ior: f32) -> Self {
+        Dielectric::with_ior(Ior::Constant(ior))
+    }
+
+    /** Creates a dielectric with a dispersive index of refraction, visible in spectral mode */
+    pub fn with_ior(ior: Ior) -> Self {
         Dielectric {
-            ior
+            ior,
+            absorption: Color::new(1.0, 1.0, 1.0),
+            density: 0.0,
+            roughness: 0.0
         }
     }
 
+    /** Sets the color light takes on after travelling one unit
+     * through the material at density 1 (Beer-Lambert law). */
+    pub fn set_absorption(&mut self, color: Color, density: f32) {
+        self.absorption = color;
+        self.density = density;
+    }
+
+    /** Roughness of the microfacet surface, 0.0 means perfectly smooth glass. */
+    pub fn set_roughness(&mut self, roughness: f32) {
+        self.roughness = roughness.clamp(0.0, 1.0);
+    }
+
     pub fn schlick_reflectance(cosine: f32, iof: f32) -> f32 {
         let mut r0 = (1.0 - iof) / (1.0 + iof);
         r0 = r0 * r0;
         let res = r0 + (1.0 - r0) * (1.0 - cosine).powi(5);
         return res;
     }
+
+    /* Fraction of light that survives the path from the ray origin to the hit point inside the material */
+    fn transmittance(&self, r_in: &Ray, hit: &HitRecord) -> Color {
+        if hit.front_face() || self.density <= 0.0 {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+
+        let distance = Vector3::distance(r_in.get_origin(), hit.p());
+        let d = self.density * distance;
+        Color::new(
+            self.absorption.r.max(0.0).powf(d),
+            self.absorption.g.max(0.0).powf(d),
+            self.absorption.b.max(0.0).powf(d)
+        )
+    }
+
+    /* Samples a GGX microfacet normal around n */
+    fn sample_microfacet(n: &Vector3, alpha: f32) -> Vector3 {
+        let u1 = crate::random::random();
+        let u2 = crate::random::random();
+
+        let tan2 = alpha * alpha * u1 / (1.0 - u1).max(1e-6);
+        let cos_theta = 1.0 / (1.0 + tan2).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+
+        let (t, s) = Vector3::orthonormal_basis(n);
+        let m = t * (sin_theta * phi.cos()) + s * (sin_theta * phi.sin()) + n * cos_theta;
+        m.normalize()
+    }
+
+    /* Smith masking term for a single direction */
+    fn smith_g1(v: &Vector3, n: &Vector3, alpha: f32) -> f32 {
+        let cos = (v * n).abs().min(1.0);
+        let tan2 = (1.0 - cos * cos).max(0.0) / (cos * cos).max(1e-8);
+        2.0 / (1.0 + (1.0 + alpha * alpha * tan2).sqrt())
+    }
+
+    fn scatter_smooth(&self, unit_dir: &Vector3, n: &Vector3, refr_ratio: f32) -> Vector3 {
+        let cosa = (&-unit_dir*n).min(1.0);
+        let sina = (1.0 - cosa*cosa).sqrt();
+        let cannot_refract = refr_ratio * sina > 1.0;
+        let reflectance = Dielectric::schlick_reflectance(cosa, refr_ratio);
+        let rand_num = crate::random::random();
+
+        if cannot_refract || reflectance > rand_num {
+            Vector3::reflection(unit_dir, n)
+        } else {
+            Vector3::refraction(unit_dir, n, refr_ratio)
+        }
+    }
+
+    /* Microfacet transmission after Walter et al., "Microfacet Models for Refraction through Rough Surfaces" */
+    fn scatter_rough(&self, unit_dir: &Vector3, n: &Vector3, refr_ratio: f32) -> Option<(Vector3, f32)> {
+        let alpha = (self.roughness * self.roughness).max(1e-4);
+        let m = Dielectric::sample_microfacet(n, alpha);
+
+        let cos_om = (&-unit_dir * &m).min(1.0);
+        if cos_om <= 0.0 {
+            return None;
+        }
+        let sin_om = (1.0 - cos_om * cos_om).sqrt();
+        let cannot_refract = refr_ratio * sin_om > 1.0;
+        let reflectance = Dielectric::schlick_reflectance(cos_om, refr_ratio);
+        let rand_num = crate::random::random();
+
+        let dir = if cannot_refract || reflectance > rand_num {
+            let d = Vector3::reflection(unit_dir, &m);
+            if &d * n <= 0.0 {
+                return None;
+            }
+            d
+        } else {
+            let d = Vector3::refraction(unit_dir, &m, refr_ratio).normalize();
+            if &d * n >= 0.0 {
+                return None;
+            }
+            d
+        };
+
+        let cos_on = (&-unit_dir * n).abs().max(1e-6);
+        let cos_mn = (&m * n).abs().max(1e-6);
+        let g = Dielectric::smith_g1(unit_dir, n, alpha) * Dielectric::smith_g1(&dir, n, alpha);
+        let weight = cos_om * g / (cos_on * cos_mn);
+
+        Some((dir, weight))
+    }
 }
 
 impl Material for Dielectric {
     fn scatter(&self, r_in: &Ray, hit: &HitRecord) -> Option<ScatterResult> {
+        let ior = self.ior.at(r_in.get_wavelength());
         let refr_ratio = if hit.front_face() {
-            1.0 / self.ior
+            1.0 / ior
         } else {
-            self.ior
+            ior
         };
 
         let unit_dir = r_in.get_direction().normalize();
-        let cosa = (&-&unit_dir*hit.n()).min(1.0);
-        let sina = (1.0 - cosa*cosa).sqrt();
-        let cannot_refract = refr_ratio * sina > 1.0;
-        let reflectance = Dielectric::schlick_reflectance(cosa, refr_ratio);
-        let rand_num: f32 = rand::random();
+        let n = hit.n();
 
-        let refracted = if cannot_refract || reflectance > rand_num {
-            Vector3::reflection(&unit_dir, &hit.n())
+        let (refracted, weight) = if self.roughness > 0.0 {
+            self.scatter_rough(&unit_dir, n, refr_ratio)?
         } else {
-            Vector3::refraction(&unit_dir, &hit.n(), refr_ratio)
+            (self.scatter_smooth(&unit_dir, n, refr_ratio), 1.0)
         };
 
         let p = hit.p();
-        let p_fixed = if hit.front_face() {
-            p.copy() + hit.n() * 0.0001
+        let (p_fixed, lobe) = if &refracted * n >= 0.0 {
+            (p.copy() + n * 0.0001, Lobe::Glossy)
         } else {
-            p.copy() - hit.n() * 0.0001
+            (p.copy() - n * 0.0001, Lobe::Transmission)
         };
-        let attenuation = Color::new(1.0, 1.0, 1.0);
+        let transmittance = self.transmittance(r_in, hit);
+        let attenuation = Color::scale(&transmittance, weight);
         let scattered = Ray::new(p_fixed, refracted);
+        let eta = if lobe == Lobe::Transmission { refr_ratio } else { 1.0 };
         Some(ScatterResult {
             ray: scattered,
-            attenuation
+            attenuation,
+            lobe,
+            eta
         })
     }
+}
 
-    fn copy(&self) -> Box<dyn Material> {
-        let mat = Dielectric {
-            ior: self.ior
-        };
-        return Box::new(mat);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispersive_index_falls_with_wavelength() {
+        let bk7 = Ior::Sellmeier([1.039_612, 0.231_792_34, 1.010_469_5], [0.006_000_699, 0.020_017_914, 103.560_65]);
+        assert!((bk7.at(None) - 1.5168).abs() < 1e-3);
+        assert!((bk7.at(Some(486.1)) - 1.5224).abs() < 1e-3);
+        assert!((bk7.at(Some(656.3)) - 1.5143).abs() < 1e-3);
+
+        let flint = Ior::Cauchy(1.728, 0.013_42);
+        assert!(flint.at(Some(400.0)) > flint.at(None));
+        assert!(flint.at(None) > flint.at(Some(700.0)));
+        assert_eq!(Ior::Constant(1.5).at(Some(400.0)), 1.5);
     }
-}
\ No newline at end of file
+}
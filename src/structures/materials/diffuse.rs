@@ -1,4 +1,6 @@
-use crate::{color::Color, ray::Ray, structures::{renderable::HitRecord, material::{ScatterResult, Material}}, math::vector3::Vector3};
+use std::f32::consts::PI;
+
+use crate::{color::Color, ray::Ray, structures::{renderable::HitRecord, material::{ScatterResult, Material, Lobe}}, math::vector3::Vector3};
 
 pub struct Diffuse {
     albedo: Color
@@ -28,14 +30,19 @@ impl Material for Diffuse {
 
         Some(ScatterResult {
             ray: scattered,
-            attenuation
+            attenuation,
+            lobe: Lobe::Diffuse,
+            eta: 1.0
         })
     }
 
-    fn copy(&self) -> Box<dyn Material> {
-        let diff = Diffuse {
-            albedo: self.albedo.copy()
-        };
-        Box::new(diff)
+    /* Lambertian reflection; scatter samples the cosine distribution */
+    fn eval(&self, _: &Vector3, wi: &Vector3, hit: &HitRecord) -> Option<(Color, f32)> {
+        let cos = (wi * hit.n()).max(0.0);
+        Some((Color::scale(&self.albedo, cos / PI), cos / PI))
+    }
+
+    fn albedo(&self, _: &HitRecord) -> Color {
+        self.albedo.copy()
     }
 }
\ No newline at end of file
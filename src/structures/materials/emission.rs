@@ -0,0 +1,36 @@
+use crate::{color::Color, ray::Ray, structures::{material::{Material, ScatterResult}, renderable::HitRecord}};
+
+/** Light source surface. Emits from its front side only and reflects nothing. */
+pub struct Emission {
+    color: Color,
+    strength: f32
+}
+
+impl Emission {
+    pub fn new(color: Color, strength: f32) -> Self {
+        Emission {
+            color, strength
+        }
+    }
+}
+
+impl Material for Emission {
+    fn scatter(&self, _: &Ray, _: &HitRecord) -> Option<ScatterResult> {
+        None
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Color {
+        if !hit.front_face() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        Color::scale(&self.color, self.strength)
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+
+    fn albedo(&self, _: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+}
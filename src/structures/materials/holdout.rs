@@ -0,0 +1,20 @@
+use crate::{ray::Ray, structures::{material::{Material, ScatterResult}, renderable::HitRecord}};
+
+/** Absorbs all light and renders as a fully transparent area when seen directly */
+pub struct Holdout {}
+
+impl Holdout {
+    pub fn new() -> Self {
+        Holdout {}
+    }
+}
+
+impl Material for Holdout {
+    fn scatter(&self, _: &Ray, _: &HitRecord) -> Option<ScatterResult> {
+        None
+    }
+
+    fn is_holdout(&self) -> bool {
+        true
+    }
+}
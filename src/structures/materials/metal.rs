@@ -1,4 +1,4 @@
-use crate::{color::Color, structures::{material::{Material, ScatterResult}, renderable::HitRecord}, math::vector3::Vector3, ray::Ray};
+use crate::{color::Color, structures::{material::{Material, ScatterResult, Lobe}, renderable::HitRecord}, math::vector3::Vector3, ray::Ray};
 
 pub struct Metal {
     albedo: Color,
@@ -33,17 +33,15 @@ impl Material for Metal {
         if scattered.get_direction() * n > 0.0 {
             return Some(ScatterResult {
                 ray: scattered,
-                attenuation
+                attenuation,
+                lobe: Lobe::Glossy,
+                eta: 1.0
             })
         }
         return None;
     }
 
-    fn copy(&self) -> Box<dyn Material> {
-        let met = Metal {
-            albedo: self.albedo.copy(),
-            roughness: self.roughness
-        };
-        Box::new(met)
+    fn albedo(&self, _: &HitRecord) -> Color {
+        self.albedo.copy()
     }
 }
\ No newline at end of file
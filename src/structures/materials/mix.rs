@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use crate::{color::Color, math::vector3::Vector3, ray::Ray, structures::{material::{Material, ScatterResult}, renderable::HitRecord, texture::Texture}};
+
+/** Blend factor of a Mix material; 0.0 picks the first material, 1.0 the second */
+pub enum MixFactor {
+    Constant(f32),
+    Texture(Arc<dyn Texture>)
+}
+
+impl MixFactor {
+    pub fn value(&self, hit: &HitRecord) -> f32 {
+        match self {
+            MixFactor::Constant(f) => *f,
+            MixFactor::Texture(t) => t.value(hit).luminance()
+        }
+    }
+
+    /* Flag of the material with all the weight, or of both if the weight can be split */
+    fn pick(&self, first: bool, second: bool) -> bool {
+        match self {
+            MixFactor::Constant(f) if *f <= 0.0 => first,
+            MixFactor::Constant(f) if *f >= 1.0 => second,
+            _ => first && second
+        }
+    }
+}
+
+pub struct Mix {
+    first: Arc<dyn Material>,
+    second: Arc<dyn Material>,
+    factor: MixFactor
+}
+
+impl Mix {
+    pub fn new(first: Arc<dyn Material>, second: Arc<dyn Material>, factor: MixFactor) -> Self {
+        Mix {
+            first, second, factor
+        }
+    }
+}
+
+impl Material for Mix {
+    fn scatter(&self, r_in: &Ray, hit: &HitRecord) -> Option<ScatterResult> {
+        let f = self.factor.value(hit).clamp(0.0, 1.0);
+        let rand_num = crate::random::random();
+
+        // Picking one of the materials with probability equal to its weight
+        // leaves the estimator unbiased, so attenuation is passed through as is.
+        if rand_num < f {
+            self.second.scatter(r_in, hit)
+        } else {
+            self.first.scatter(r_in, hit)
+        }
+    }
+
+    /* Both materials have to be evaluated for the blend to be, otherwise it's sampled only */
+    fn eval(&self, wo: &Vector3, wi: &Vector3, hit: &HitRecord) -> Option<(Color, f32)> {
+        let f = self.factor.value(hit).clamp(0.0, 1.0);
+        let (f_first, pdf_first) = self.first.eval(wo, wi, hit)?;
+        let (f_second, pdf_second) = self.second.eval(wo, wi, hit)?;
+        Some((Color::add(&Color::scale(&f_first, 1.0 - f), &Color::scale(&f_second, f)), pdf_first * (1.0 - f) + pdf_second * f))
+    }
+
+    fn emitted(&self, hit: &HitRecord) -> Color {
+        let f = self.factor.value(hit).clamp(0.0, 1.0);
+        Color::add(&Color::scale(&self.first.emitted(hit), 1.0 - f), &Color::scale(&self.second.emitted(hit), f))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.first.is_emissive() || self.second.is_emissive()
+    }
+
+    fn is_holdout(&self) -> bool {
+        self.factor.pick(self.first.is_holdout(), self.second.is_holdout())
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        self.factor.pick(self.first.is_shadow_catcher(), self.second.is_shadow_catcher())
+    }
+
+    fn alpha(&self, hit: &HitRecord) -> f32 {
+        let f = self.factor.value(hit).clamp(0.0, 1.0);
+        self.first.alpha(hit) * (1.0 - f) + self.second.alpha(hit) * f
+    }
+
+    fn albedo(&self, hit: &HitRecord) -> Color {
+        let f = self.factor.value(hit).clamp(0.0, 1.0);
+        Color::add(&Color::scale(&self.first.albedo(hit), 1.0 - f), &Color::scale(&self.second.albedo(hit), f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::vector2::Vector2, structures::{material_library::MaterialLibrary, materials::{diffuse::Diffuse, emission::Emission, holdout::Holdout, metal::Metal}}};
+
+    #[test]
+    fn mix_blends_light_and_evaluation() {
+        let diffuse: Arc<dyn Material> = Arc::new(Diffuse::new(Color::new(0.8, 0.8, 0.8)));
+        let lamp: Arc<dyn Material> = Arc::new(Emission::new(Color::new(1.0, 1.0, 1.0), 4.0));
+        let id = MaterialLibrary::new().add("diffuse", diffuse.clone());
+        let hit = HitRecord::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), 1.0, true, Vector2::new(0.0, 0.0), id);
+
+        let glowing = Mix::new(diffuse.clone(), lamp.clone(), MixFactor::Constant(0.25));
+        assert!(glowing.is_emissive());
+        let expected = Color::scale(&lamp.emitted(&hit), 0.25);
+        assert!((glowing.emitted(&hit).r - expected.r).abs() < 1e-6);
+
+        let wo = Vector3::new(0.0, 1.0, 0.0);
+        let wi = Vector3::new(1.0, 1.0, 0.0).normalize();
+        let darker: Arc<dyn Material> = Arc::new(Diffuse::new(Color::new(0.2, 0.2, 0.2)));
+        let (f, pdf) = Mix::new(diffuse.clone(), darker.clone(), MixFactor::Constant(0.5)).eval(&wo, &wi, &hit).unwrap();
+        let (f_first, pdf_first) = diffuse.eval(&wo, &wi, &hit).unwrap();
+        let (f_second, _) = darker.eval(&wo, &wi, &hit).unwrap();
+        assert!((f.r - 0.5 * (f_first.r + f_second.r)).abs() < 1e-6);
+        assert!((pdf - pdf_first).abs() < 1e-6);
+
+        // A mirror can't be evaluated, so neither can a blend with it
+        let mirror: Arc<dyn Material> = Arc::new(Metal::new(Color::new(1.0, 1.0, 1.0), 0.0));
+        assert!(Mix::new(diffuse.clone(), mirror, MixFactor::Constant(0.5)).eval(&wo, &wi, &hit).is_none());
+    }
+
+    #[test]
+    fn mix_is_a_holdout_where_it_all_is() {
+        let diffuse: Arc<dyn Material> = Arc::new(Diffuse::new(Color::new(0.8, 0.8, 0.8)));
+        let holdout: Arc<dyn Material> = Arc::new(Holdout::new());
+        assert!(Mix::new(diffuse.clone(), holdout.clone(), MixFactor::Constant(1.0)).is_holdout());
+        assert!(!Mix::new(diffuse.clone(), holdout.clone(), MixFactor::Constant(0.5)).is_holdout());
+        assert!(Mix::new(holdout.clone(), holdout, MixFactor::Constant(0.5)).is_holdout());
+    }
+}
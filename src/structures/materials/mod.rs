@@ -1,3 +1,10 @@
 pub mod diffuse;
 pub mod metal;
-pub mod dielectric;
\ No newline at end of file
+pub mod dielectric;
+pub mod mix;
+pub mod coated;
+pub mod subsurface;
+pub mod holdout;
+pub mod shadow_catcher;
+pub mod cutout;
+pub mod boundary;pub mod emission;
@@ -0,0 +1,47 @@
+use std::f32::consts::PI;
+
+use crate::{color::Color, ray::Ray, structures::{material::{Material, ScatterResult, Lobe}, renderable::HitRecord}, math::vector3::Vector3};
+
+/** Ground for compositing: seen by the camera it only records the shadows
+ * cast onto it, while in reflections and bounces it acts as a diffuse surface. */
+pub struct ShadowCatcher {
+    albedo: Color
+}
+
+impl ShadowCatcher {
+    pub fn new(albedo: Color) -> Self {
+        ShadowCatcher {
+            albedo
+        }
+    }
+}
+
+impl Material for ShadowCatcher {
+    fn scatter(&self, _: &Ray, hit: &HitRecord) -> Option<ScatterResult> {
+        let n = hit.n();
+        let mut scattered_dir = n + &Vector3::random_in_unit_sphere();
+        if scattered_dir.near_zero() {
+            scattered_dir = n.copy();
+        }
+
+        Some(ScatterResult {
+            ray: Ray::new(hit.p().copy() + n * 0.0001, scattered_dir),
+            attenuation: self.albedo.copy(),
+            lobe: Lobe::Diffuse,
+            eta: 1.0
+        })
+    }
+
+    fn eval(&self, _: &Vector3, wi: &Vector3, hit: &HitRecord) -> Option<(Color, f32)> {
+        let cos = (wi * hit.n()).max(0.0);
+        Some((Color::scale(&self.albedo, cos / PI), cos / PI))
+    }
+
+    fn is_shadow_catcher(&self) -> bool {
+        true
+    }
+
+    fn albedo(&self, _: &HitRecord) -> Color {
+        self.albedo.copy()
+    }
+}
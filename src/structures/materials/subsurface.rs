@@ -0,0 +1,181 @@
+use crate::{color::Color, math::vector3::Vector3, ray::Ray, structures::{material::{Material, ScatterResult, Lobe}, renderable::HitRecord}};
+
+use super::dielectric::Dielectric;
+
+/** Random walk subsurface scattering inside closed objects (skin, wax, marble, milk).
+ * The walk is driven by the regular scatter loop: every segment that ends on the
+ * inside of the surface is checked for a scattering event along the way. */
+pub struct Subsurface {
+    sigma_t: [f32; 3],
+    albedo: [f32; 3],
+    ior: f32
+}
+
+impl Subsurface {
+    /** `color` is the overall color of the material, `radius` the average
+     * distance light travels under the surface per color channel. The more the
+     * radii differ, the noisier the walks get, as every channel is weighted
+     * against distances sampled for all three. */
+    pub fn new(color: Color, radius: Color, ior: f32) -> Self {
+        let radius = [radius.r, radius.g, radius.b];
+        let color = [color.r, color.g, color.b];
+
+        let mut sigma_t = [0.0; 3];
+        let mut albedo = [0.0; 3];
+        for i in 0..3 {
+            sigma_t[i] = 1.0 / radius[i].max(1e-4);
+            albedo[i] = Subsurface::single_scatter_albedo(color[i]);
+        }
+
+        Subsurface {
+            sigma_t, albedo, ior
+        }
+    }
+
+    /* Inverts multiple scattering albedo to single scattering albedo,
+     * fit from Chiang et al., "Practical and Controllable Subsurface Scattering for Production Path Tracing" */
+    fn single_scatter_albedo(a: f32) -> f32 {
+        let a = a.clamp(0.0, 0.999);
+        let s = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+        1.0 - s * s
+    }
+
+    /* Specular interface between the medium and the outside, returns the ray leaving it and the
+     * ratio of the indices of refraction if it was refracted */
+    fn cross_boundary(&self, unit_dir: &Vector3, hit: &HitRecord) -> (Ray, f32) {
+        let n = hit.n();
+        let refr_ratio = if hit.front_face() {
+            1.0 / self.ior
+        } else {
+            self.ior
+        };
+
+        let cosa = (&-unit_dir * n).min(1.0);
+        let sina = (1.0 - cosa * cosa).sqrt();
+        let cannot_refract = refr_ratio * sina > 1.0;
+        let reflectance = Dielectric::schlick_reflectance(cosa, refr_ratio);
+        let rand_num = crate::random::random();
+
+        let (dir, eta) = if cannot_refract || reflectance > rand_num {
+            (Vector3::reflection(unit_dir, n), 1.0)
+        } else {
+            (Vector3::refraction(unit_dir, n, refr_ratio), refr_ratio)
+        };
+
+        let p_fixed = if &dir * n >= 0.0 {
+            hit.p().copy() + n * 0.0001
+        } else {
+            hit.p().copy() - n * 0.0001
+        };
+        (Ray::new(p_fixed, dir), eta)
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, r_in: &Ray, hit: &HitRecord) -> Option<ScatterResult> {
+        let unit_dir = r_in.get_direction().normalize();
+
+        if hit.front_face() {
+            let (ray, eta) = self.cross_boundary(&unit_dir, hit);
+            return Some(ScatterResult {
+                ray,
+                attenuation: Color::new(1.0, 1.0, 1.0),
+                lobe: Lobe::Transmission,
+                eta
+            });
+        }
+
+        // The segment from the ray origin to the hit point lies inside the medium.
+        // The free flight distance is sampled from one randomly picked channel and
+        // weighted against the average pdf of all three.
+        let d = Vector3::distance(r_in.get_origin(), hit.p());
+        let channel = ((crate::random::random() * 3.0) as usize).min(2);
+        let u = crate::random::random();
+        let s = -(1.0 - u).ln() / self.sigma_t[channel];
+
+        let mut weight = [0.0; 3];
+        if s < d {
+            let mut pdf = 0.0;
+            for (i, w) in weight.iter_mut().enumerate() {
+                let tr = (-self.sigma_t[i] * s).exp();
+                *w = self.albedo[i] * self.sigma_t[i] * tr;
+                pdf += self.sigma_t[i] * tr / 3.0;
+            }
+            let attenuation = Color::new(weight[0] / pdf, weight[1] / pdf, weight[2] / pdf);
+            let p = r_in.get_origin() + &(&unit_dir * s);
+
+            return Some(ScatterResult {
+                ray: Ray::new(p, Vector3::random_in_unit_sphere()),
+                attenuation,
+                lobe: Lobe::Volume,
+                eta: 1.0
+            });
+        }
+
+        let mut pdf = 0.0;
+        for (w, sigma_t) in weight.iter_mut().zip(self.sigma_t) {
+            *w = (-sigma_t * d).exp();
+            pdf += *w / 3.0;
+        }
+        if pdf <= 0.0 {
+            return None;
+        }
+        let attenuation = Color::new(weight[0] / pdf, weight[1] / pdf, weight[2] / pdf);
+
+        let (ray, eta) = self.cross_boundary(&unit_dir, hit);
+        Some(ScatterResult {
+            ray,
+            attenuation,
+            lobe: Lobe::Transmission,
+            eta
+        })
+    }
+
+    fn albedo(&self, _: &HitRecord) -> Color {
+        Color::new(self.albedo[0], self.albedo[1], self.albedo[2])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{math::matrix4::Matrix4, structures::{material_library::MaterialLibrary, renderable::Renderable, sphere::Sphere}};
+
+    /* Average throughput of random walks entering a sphere of the material that leave it again */
+    fn escaping_energy(material: Subsurface) -> f32 {
+        let material = Arc::new(material);
+        let sphere = Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0, MaterialLibrary::new().add("sss", material.clone()));
+        let identity = Matrix4::identity();
+
+        let n = 2000;
+        let mut energy = 0.0;
+        for _ in 0..n {
+            let mut ray = Ray::new(Vector3::new(0.0, 0.0, 3.0), Vector3::random_in_unit_sphere() * 0.3 - Vector3::new(0.0, 0.0, 1.0));
+            let mut throughput = Color::new(1.0, 1.0, 1.0);
+            for _ in 0..100000 {
+                let hit = match sphere.trace(&ray, 0.0001, 10000.0, 0.0, &identity) {
+                    Some(h) => h,
+                    None => {
+                        energy += throughput.luminance();
+                        break;
+                    }
+                };
+                let scatter = material.scatter(&ray, &hit).unwrap();
+                throughput = &throughput * &scatter.attenuation;
+                ray = scatter.ray;
+            }
+        }
+        return energy / n as f32;
+    }
+
+    #[test]
+    fn white_material_without_absorption_conserves_energy() {
+        let white = Subsurface::new(Color::new(1.0, 1.0, 1.0), Color::new(0.1, 0.1, 0.1), 1.4);
+        assert!((escaping_energy(white) - 1.0).abs() < 0.01);
+
+        let grey = Subsurface::new(Color::new(0.5, 0.5, 0.5), Color::new(0.2, 0.2, 0.2), 1.4);
+        assert!(escaping_energy(grey) < 0.8);
+    }
+}
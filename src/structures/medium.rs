@@ -0,0 +1,76 @@
+use std::f32::consts::PI;
+
+use crate::{color::Color, math::vector3::Vector3, ray::Ray};
+
+/** Handle of a medium stored in the scene */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MediumId(usize);
+
+impl MediumId {
+    pub fn new(index: usize) -> Self {
+        MediumId(index)
+    }
+
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+/** Outcome of sampling a ray segment through a medium. Weights already
+ * include the division by the sampling probability. */
+pub enum MediumSample {
+    /** The ray collided with a particle at point p and has to be scattered */
+    Scattered {
+        p: Vector3,
+        weight: Color,
+        emission: Color
+    },
+    /** The ray reached the end of the segment */
+    Passed {
+        weight: Color,
+        emission: Color
+    }
+}
+
+pub trait Medium: Send + Sync {
+    /** Samples the segment of the ray between t = 0 and t = t_max */
+    fn sample(&self, ray: &Ray, t_max: f32) -> MediumSample;
+
+    /** Estimated fraction of light that gets through the segment unscattered */
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> Color;
+
+    fn phase(&self) -> &HenyeyGreenstein;
+}
+
+/** Henyey-Greenstein phase function, g > 0 scatters forward, g < 0 backward */
+pub struct HenyeyGreenstein {
+    g: f32
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f32) -> Self {
+        HenyeyGreenstein {
+            g: g.clamp(-0.99, 0.99)
+        }
+    }
+
+    /** Samples an outgoing direction proportionally to the phase function */
+    pub fn sample(&self, dir: &Vector3) -> Vector3 {
+        let g = self.g;
+        let u1 = crate::random::random();
+        let u2 = crate::random::random();
+
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u1
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u1);
+            (1.0 + g * g - s * s) / (2.0 * g)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+
+        let w = dir.normalize();
+        let (t, s) = Vector3::orthonormal_basis(&w);
+        t * (sin_theta * phi.cos()) + s * (sin_theta * phi.sin()) + w * cos_theta
+    }
+}
@@ -4,4 +4,11 @@ pub mod scene;
 pub mod node;
 pub mod camera;
 pub mod material;
-pub mod materials;
\ No newline at end of file
+pub mod material_library;
+pub mod materials;
+pub mod texture;
+pub mod textures;
+pub mod medium;
+pub mod volumes;
+pub mod environment;
+pub mod environments;
\ No newline at end of file
@@ -1,11 +1,17 @@
-use std::{collections::HashMap, rc::Rc, cell::{RefCell}, sync::{Arc, RwLock}, borrow::BorrowMut};
+use std::{collections::HashMap, rc::Rc, cell::{RefCell}, sync::{Arc, RwLock, atomic::{AtomicU64, Ordering}}, borrow::BorrowMut};
 
 use crate::{math::{vector3::Vector3, matrix4::Matrix4}, animation::animation::AnimationChannel};
 
-use super::{renderable::Renderable};
+use super::{renderable::Renderable, medium::MediumId};
+
+/* Next identifier handed out to a node, 0 is left for the background */
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
 
 pub struct Node {
+    id: u64,
+    name: String,
     renderable: Option<Box<dyn Renderable + Send + Sync>>,
+    interior: Option<MediumId>,
     translation: Vector3,
     rotation: Vector3,
     scale: Vector3,
@@ -17,8 +23,12 @@ pub struct Node {
 
 impl Node {
     pub fn new() -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
         Node {
+            id,
+            name: format!("node{}", id),
             renderable: None,
+            interior: None,
             translation: Vector3::new(0.0, 0.0, 0.0),
             rotation: Vector3::new(0.0, 0.0, 0.0),
             scale: Vector3::new(1.0, 1.0, 1.0),
@@ -29,6 +39,20 @@ impl Node {
         }
     }
 
+    /** Unique identifier of the node, written to the node ID render pass */
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /** Name of the node, used for cryptomatte mattes. Defaults to "node" followed by the ID. */
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
     pub fn set_parent(&mut self, p: Arc<RwLock<Node>>) {
         self.parent = Some(p);
     }
@@ -124,4 +148,13 @@ impl Node {
     pub fn renderable(&self) -> &Option<Box<dyn Renderable + Send + Sync>> {
         &self.renderable
     }
+
+    /** Fills the inside of the node's renderable with a medium. The renderable should be closed. */
+    pub fn set_interior_medium(&mut self, medium: MediumId) {
+        self.interior = Some(medium);
+    }
+
+    pub fn interior_medium(&self) -> Option<MediumId> {
+        self.interior
+    }
 }
\ No newline at end of file
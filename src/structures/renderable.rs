@@ -1,19 +1,24 @@
-use crate::{ray::Ray, math::{vector3::Vector3, matrix4::Matrix4}, color::Color};
+use crate::{ray::Ray, math::{vector2::Vector2, vector3::Vector3, matrix4::Matrix4}, color::Color};
 
-use super::material::{Material};
+use super::{material_library::MaterialId, medium::MediumId};
 
 pub struct HitRecord {
     p: Vector3,
     n: Vector3,
     t: f32,
     front_face: bool,
-    material: Box<dyn Material>
+    uv: Vector2,
+    material: MaterialId,
+    interior: Option<MediumId>,
+    node: u64
 }
 
 impl HitRecord {
-    pub fn new(p: Vector3, n: Vector3, t: f32, front_face: bool, material: Box<dyn Material>) -> Self {
+    pub fn new(p: Vector3, n: Vector3, t: f32, front_face: bool, uv: Vector2, material: MaterialId) -> Self {
         HitRecord {
-            p, n, t, front_face, material
+            p, n, t, front_face, uv, material,
+            interior: None,
+            node: 0
         }
     }
 
@@ -33,12 +38,45 @@ impl HitRecord {
         self.front_face
     }
 
-    pub fn material(&self) -> &Box<dyn Material> {
-        &self.material
+    pub fn uv(&self) -> &Vector2 {
+        &self.uv
+    }
+
+    pub fn material(&self) -> MaterialId {
+        self.material
+    }
+
+    /** Medium filling the inside of the object that was hit */
+    pub fn interior(&self) -> Option<MediumId> {
+        self.interior
+    }
+
+    pub fn set_interior(&mut self, interior: Option<MediumId>) {
+        self.interior = interior;
+    }
+
+    /** Identifier of the node holding the object that was hit */
+    pub fn node(&self) -> u64 {
+        self.node
+    }
+
+    pub fn set_node(&mut self, node: u64) {
+        self.node = node;
     }
 }
 
 pub trait Renderable {
     fn trace(&self, ray: &Ray, t_min: f32, t_max: f32, f: f32, m: &Matrix4) -> Option<HitRecord>;
     fn get_color(&self, hit_record: &HitRecord) -> Color;
+    fn material(&self) -> MaterialId;
+
+    /** Picks a uniformly distributed point on the surface, used to sample emissive objects.
+     * The returned record faces outwards and has t set to 0. */
+    fn sample_surface(&self, _f: f32, _m: &Matrix4) -> Option<HitRecord> {
+        None
+    }
+
+    fn area(&self, _f: f32) -> f32 {
+        0.0
+    }
 }
\ No newline at end of file
@@ -1,19 +1,32 @@
 use std::{sync::{Arc, RwLock}};
 
-use crate::{ray::Ray};
+use crate::{ray::Ray, color::Color, math::vector3::Vector3, stats};
 
-use super::{node::Node, renderable::HitRecord, camera::Camera};
+use super::{node::Node, renderable::HitRecord, camera::Camera, material::Material, material_library::{MaterialLibrary, MaterialId}, medium::{Medium, MediumId}, environment::Environment, environments::gradient::Gradient};
 
 pub struct Scene {
     children: Vec<Arc<RwLock<Node>>>,
-    cameras: Vec<Camera>
+    cameras: Vec<Camera>,
+    materials: MaterialLibrary,
+    media: Vec<Arc<dyn Medium>>,
+    medium: Option<MediumId>,
+    environment: Box<dyn Environment>,
+    lights: Vec<Arc<RwLock<Node>>>
 }
 
 impl Scene {
     pub fn new() -> Self {
         Scene {
             children: Vec::new(),
-            cameras: Vec::new()
+            cameras: Vec::new(),
+            materials: MaterialLibrary::new(),
+            media: Vec::new(),
+            medium: None,
+            environment: Box::new(Gradient::new(
+                Color::new(1.0, 1.0, 1.0),
+                Color::new(0.5, 0.7, 1.0)
+            )),
+            lights: Vec::new()
         }
     }
 
@@ -29,7 +42,53 @@ impl Scene {
         return self.cameras.get(index);
     }
 
+    pub fn materials(&self) -> &MaterialLibrary {
+        &self.materials
+    }
+
+    pub fn materials_mut(&mut self) -> &mut MaterialLibrary {
+        &mut self.materials
+    }
+
+    pub fn material(&self, id: MaterialId) -> &dyn Material {
+        self.materials.get(id).as_ref()
+    }
+
+    pub fn add_medium(&mut self, medium: Arc<dyn Medium>) -> MediumId {
+        self.media.push(medium);
+        return MediumId::new(self.media.len() - 1);
+    }
+
+    pub fn medium(&self, id: MediumId) -> &dyn Medium {
+        self.media[id.index()].as_ref()
+    }
+
+    /** Sets the medium filling the whole scene outside of any node interiors */
+    pub fn set_global_medium(&mut self, medium: Option<MediumId>) {
+        self.medium = medium;
+    }
+
+    /** Medium the ray travelled through before reaching the hit (or escaping the scene) */
+    pub fn segment_medium(&self, hit: Option<&HitRecord>) -> Option<&dyn Medium> {
+        let id = match hit {
+            Some(h) if !h.front_face() && h.interior().is_some() => h.interior(),
+            _ => self.medium
+        };
+        id.map(|i| self.medium(i))
+    }
+
+    pub fn set_environment(&mut self, environment: Box<dyn Environment>) {
+        self.environment = environment;
+    }
+
+    pub fn environment(&self) -> &dyn Environment {
+        self.environment.as_ref()
+    }
+
     pub fn update_transforms(&mut self, f: f32) {
+        self.environment.update(f);
+
+        let mut lights = Vec::new();
         let mut stack: Vec<Arc<RwLock<Node>>> = Vec::new();
 
         for i in &self.children {
@@ -42,13 +101,100 @@ impl Scene {
             node.update_transforms_per_frame(f);
             stack.pop();
 
+            if let Some(rend) = node.renderable() {
+                if self.materials.get(rend.material()).is_emissive() {
+                    lights.push(node_arc.clone());
+                }
+            }
+
             for i in node.get_children() {
                 stack.push(i.clone());
             }
         }
+
+        self.lights = lights;
+    }
+
+    /** Names of all nodes in the scene by their IDs */
+    pub fn node_names(&self) -> Vec<(u64, String)> {
+        let mut names = Vec::new();
+        let mut stack: Vec<Arc<RwLock<Node>>> = Vec::new();
+
+        for i in &self.children {
+            stack.push(i.clone());
+        }
+
+        while let Some(node_arc) = stack.pop() {
+            let node = node_arc.read().unwrap();
+            names.push((node.id(), node.name().to_string()));
+
+            for i in node.get_children() {
+                stack.push(i.clone());
+            }
+        }
+
+        return names;
+    }
+
+    /** Picks a point on one of the emissive objects, returns it with its pdf per unit area */
+    pub fn sample_light(&self, f: f32) -> Option<(HitRecord, f32)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let index = ((crate::random::random() * self.lights.len() as f32) as usize).min(self.lights.len() - 1);
+        let node = self.lights[index].read().unwrap();
+        let rend = node.renderable().as_ref()?;
+        let area = rend.area(f);
+        if area <= 0.0 {
+            return None;
+        }
+
+        let mut sample = rend.sample_surface(f, node.get_transform_matrix())?;
+        sample.set_interior(node.interior_medium());
+        return Some((sample, 1.0 / (area * self.lights.len() as f32)));
+    }
+
+    /** Solid angle pdf of sample_light choosing the point where the ray hit an emissive object */
+    pub fn light_pdf(&self, ray: &Ray, hit: &HitRecord, f: f32) -> f32 {
+        let pdf_area = self.light_area_pdf(ray, hit, f);
+        let dir = ray.get_direction().normalize();
+        let cos = (&dir * hit.n()).abs();
+        if pdf_area <= 0.0 || cos <= 0.0 {
+            return 0.0;
+        }
+        let dist = Vector3::distance(ray.get_origin(), hit.p());
+        return pdf_area * dist * dist / cos;
+    }
+
+    /** Pdf per unit area of sample_light choosing the point where the ray hit an emissive object */
+    pub fn light_area_pdf(&self, ray: &Ray, hit: &HitRecord, f: f32) -> f32 {
+        for light in &self.lights {
+            let node = light.read().unwrap();
+            let rend = match node.renderable() {
+                Some(r) => r,
+                None => continue
+            };
+
+            let same_point = match rend.trace(ray, 0.0001, 10000.0, f, node.get_transform_matrix()) {
+                Some(h) => Vector3::distance(h.p(), hit.p()) < 1e-3,
+                None => false
+            };
+            if !same_point {
+                continue;
+            }
+
+            let area = rend.area(f);
+            if area <= 0.0 {
+                return 0.0;
+            }
+            return 1.0 / (area * self.lights.len() as f32);
+        }
+        return 0.0;
     }
 
     pub fn trace(&self, ray: &Ray, t_min: f32, t_max: f32, f: f32) -> Option<HitRecord> {
+        stats::add_ray();
         let mut hit_opt = None;
 
         let mut stack: Vec<Arc<RwLock<Node>>> = Vec::new();
@@ -81,7 +227,12 @@ impl Scene {
         }
 
         let rend = rend_opt.as_ref().unwrap();
-        let new_hit_opt = rend.trace(ray, t_min, t_max, f, node.get_transform_matrix());
+        stats::add_intersection_test();
+        let mut new_hit_opt = rend.trace(ray, t_min, t_max, f, node.get_transform_matrix());
+        if let Some(h) = new_hit_opt.as_mut() {
+            h.set_interior(node.interior_medium());
+            h.set_node(node.id());
+        }
 
         if hit_opt.is_none() {
             return new_hit_opt;
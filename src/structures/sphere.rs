@@ -1,18 +1,18 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, f32::consts::PI};
 
-use crate::{math::{vector3::Vector3, matrix4::Matrix4, vector4::Vector4}, ray::Ray, color::Color, animation::animation::AnimationChannel};
+use crate::{math::{vector2::Vector2, vector3::Vector3, matrix4::Matrix4, vector4::Vector4}, ray::Ray, color::Color, animation::animation::AnimationChannel};
 
-use super::{renderable::{Renderable, HitRecord}, material::Material};
+use super::{renderable::{Renderable, HitRecord}, material_library::MaterialId};
 
 pub struct Sphere {
     center: Vector3,
     radius: f32,
-    material: Box<dyn Material + Send + Sync>,
+    material: MaterialId,
     animation_channels: HashMap<String, AnimationChannel>
 }
 
 impl Sphere {
-    pub fn new(center: Vector3, radius: f32, material: Box<dyn Material + Send + Sync>) -> Sphere {
+    pub fn new(center: Vector3, radius: f32, material: MaterialId) -> Sphere {
         Sphere {
             center,
             radius,
@@ -25,10 +25,11 @@ impl Sphere {
         self.animation_channels.insert(name, ch);
     }
 
-    pub fn normal(&self, p: &Vector3) -> Vector3 {
-        let diff = Vector3::diff(p, &self.center);
-        let n = diff * (1.0 / self.radius);
-        return n;
+    /* Spherical mapping of the outward normal to texture coordinates */
+    pub fn uv(n: &Vector3) -> Vector2 {
+        let theta = (-n.y).clamp(-1.0, 1.0).acos();
+        let phi = (-n.z).atan2(n.x) + PI;
+        Vector2::new(phi / (2.0 * PI), theta / PI)
     }
 
     pub fn get_center_by_frame(&self, f: f32) -> Vector3 {
@@ -79,7 +80,8 @@ impl Renderable for Sphere {
         }
 
         let point = ray.at(t);
-        let mut v_n = self.normal(&point);
+        let mut v_n = (&point - v_sc) * (1.0 / sr);
+        let uv = Sphere::uv(&v_n);
         let is_front_face = if v_rd * &v_n >= 0.0 {
             v_n = v_n * -1.0;
             false
@@ -88,7 +90,7 @@ impl Renderable for Sphere {
         };
 
         return Some(
-            HitRecord::new(point, v_n, t, is_front_face, self.material.copy())
+            HitRecord::new(point, v_n, t, is_front_face, uv, self.material)
         );
     }
 
@@ -100,4 +102,25 @@ impl Renderable for Sphere {
             b: n.z * 0.5 + 0.5
         }
     }
+
+    fn material(&self) -> MaterialId {
+        self.material
+    }
+
+    fn sample_surface(&self, f: f32, m: &Matrix4) -> Option<HitRecord> {
+        let v_sc_4 = Matrix4::mul_vector4(m,
+            &Vector4::from_vector3(&self.get_center_by_frame(f), 1.0));
+        let v_sc = Vector3::new(v_sc_4.x, v_sc_4.y, v_sc_4.z);
+        let sr = self.get_radius_by_frame(f);
+
+        let n = Vector3::random_in_unit_sphere();
+        let point = v_sc + &n * sr.abs();
+        let uv = Sphere::uv(&n);
+        return Some(HitRecord::new(point, n, 0.0, true, uv, self.material));
+    }
+
+    fn area(&self, f: f32) -> f32 {
+        let sr = self.get_radius_by_frame(f);
+        4.0 * PI * sr * sr
+    }
 }
\ No newline at end of file
@@ -0,0 +1,7 @@
+use crate::color::Color;
+
+use super::renderable::HitRecord;
+
+pub trait Texture: Send + Sync {
+    fn value(&self, hit: &HitRecord) -> Color;
+}
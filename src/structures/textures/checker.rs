@@ -0,0 +1,30 @@
+use crate::{color::Color, structures::{texture::Texture, renderable::HitRecord}};
+
+/** Checkerboard in texture space, with `scale` squares along each axis */
+pub struct Checker {
+    even: Color,
+    odd: Color,
+    scale: f32
+}
+
+impl Checker {
+    pub fn new(even: Color, odd: Color, scale: f32) -> Self {
+        Checker {
+            even, odd, scale
+        }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, hit: &HitRecord) -> Color {
+        let uv = hit.uv();
+        let u = (uv.x * self.scale).floor() as i64;
+        let v = (uv.y * self.scale).floor() as i64;
+
+        if (u + v) % 2 == 0 {
+            self.even.copy()
+        } else {
+            self.odd.copy()
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{color::Color, ray::Ray, structures::medium::{Medium, MediumSample, HenyeyGreenstein}};
+
+/** Medium with constant density, e.g. fog or milky water */
+pub struct Homogeneous {
+    sigma_a: [f32; 3],
+    sigma_s: [f32; 3],
+    emission: Color,
+    phase: HenyeyGreenstein
+}
+
+impl Homogeneous {
+    /** Absorption and scattering coefficients are given per unit of distance */
+    pub fn new(sigma_a: Color, sigma_s: Color, g: f32) -> Self {
+        Homogeneous {
+            sigma_a: [sigma_a.r.max(0.0), sigma_a.g.max(0.0), sigma_a.b.max(0.0)],
+            sigma_s: [sigma_s.r.max(0.0), sigma_s.g.max(0.0), sigma_s.b.max(0.0)],
+            emission: Color::new(0.0, 0.0, 0.0),
+            phase: HenyeyGreenstein::new(g)
+        }
+    }
+
+    /** Light emitted by the absorbing part of the medium, e.g. for fire */
+    pub fn set_emission(&mut self, emission: Color) {
+        self.emission = emission;
+    }
+
+    fn sigma_t(&self, i: usize) -> f32 {
+        self.sigma_a[i] + self.sigma_s[i]
+    }
+}
+
+impl Medium for Homogeneous {
+    fn sample(&self, ray: &Ray, t_max: f32) -> MediumSample {
+        let len = ray.get_direction().length();
+        let d_max = t_max * len;
+
+        // Distance is sampled with one randomly chosen channel and
+        // weighted by the average pdf of all three channels.
+        let channel = ((crate::random::random() * 3.0) as usize).min(2);
+        let sigma_c = self.sigma_t(channel);
+        let s = if sigma_c > 0.0 {
+            -(1.0 - crate::random::random()).ln() / sigma_c
+        } else {
+            f32::INFINITY
+        };
+
+        let mut tr = [0.0; 3];
+        if s < d_max {
+            let mut pdf = 0.0;
+            for (i, t) in tr.iter_mut().enumerate() {
+                *t = (-self.sigma_t(i) * s).exp();
+                pdf += self.sigma_t(i) * *t / 3.0;
+            }
+            let weight = Color::new(
+                self.sigma_s[0] * tr[0] / pdf,
+                self.sigma_s[1] * tr[1] / pdf,
+                self.sigma_s[2] * tr[2] / pdf
+            );
+            let emission = Color::new(
+                self.emission.r * self.sigma_a[0] * tr[0] / pdf,
+                self.emission.g * self.sigma_a[1] * tr[1] / pdf,
+                self.emission.b * self.sigma_a[2] * tr[2] / pdf
+            );
+            let p = ray.at(s / len);
+
+            return MediumSample::Scattered {
+                p, weight, emission
+            };
+        }
+
+        let mut prob = 0.0;
+        for (i, t) in tr.iter_mut().enumerate() {
+            *t = (-self.sigma_t(i) * d_max).exp();
+            prob += *t / 3.0;
+        }
+        let weight = if prob > 0.0 {
+            Color::new(tr[0] / prob, tr[1] / prob, tr[2] / prob)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+
+        MediumSample::Passed {
+            weight,
+            emission: Color::new(0.0, 0.0, 0.0)
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> Color {
+        let d = t_max * ray.get_direction().length();
+        Color::new(
+            (-self.sigma_t(0) * d).exp(),
+            (-self.sigma_t(1) * d).exp(),
+            (-self.sigma_t(2) * d).exp()
+        )
+    }
+
+    fn phase(&self) -> &HenyeyGreenstein {
+        &self.phase
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vector3::Vector3;
+
+    #[test]
+    fn tracking_matches_beer_lambert() {
+        let medium = Homogeneous::new(Color::new(0.1, 0.2, 0.5), Color::new(0.1, 0.3, 0.5), 0.0);
+        // Two units long, with a direction that isn't normalized
+        let ray = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -0.5));
+        let expected = [(-0.4_f32).exp(), (-1.0_f32).exp(), (-2.0_f32).exp()];
+
+        let tr = medium.transmittance(&ray, 4.0);
+        assert!((tr.r - expected[0]).abs() < 1e-5 && (tr.g - expected[1]).abs() < 1e-5 && (tr.b - expected[2]).abs() < 1e-5);
+
+        // Passing weights average out to the transmittance of each channel
+        let n = 50000;
+        let mut sum = [0.0; 3];
+        for _ in 0..n {
+            if let MediumSample::Passed { weight, .. } = medium.sample(&ray, 4.0) {
+                sum[0] += weight.r;
+                sum[1] += weight.g;
+                sum[2] += weight.b;
+            }
+        }
+        for i in 0..3 {
+            assert!((sum[i] / n as f32 - expected[i]).abs() < 0.02);
+        }
+    }
+}
@@ -0,0 +1,2 @@
+pub mod homogeneous;
+pub mod grid;
@@ -0,0 +1,162 @@
+/** Order in which the tiles of an image are rendered */
+#[derive(Clone, Copy, PartialEq)]
+pub enum TileOrder {
+    /** Row by row from the top */
+    Scanline,
+    /** Outwards from the center of the image, where the subject usually is */
+    Spiral,
+    /** Along a Hilbert curve, which keeps consecutive tiles close together */
+    Hilbert
+}
+
+impl TileOrder {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "scanline" => Some(TileOrder::Scanline),
+            "spiral" => Some(TileOrder::Spiral),
+            "hilbert" => Some(TileOrder::Hilbert),
+            _ => None
+        }
+    }
+}
+
+/** Rectangle of pixels rendered as a unit of work, ends excluded */
+#[derive(Clone, Copy)]
+pub struct Tile {
+    pub x_start: u64,
+    pub x_end: u64,
+    pub y_start: u64,
+    pub y_end: u64
+}
+
+/** Splits an area of an image into square tiles of the given size, smaller at the right and
+ * bottom edges, listed in the given order */
+pub fn tiles(area: &Tile, size: u64, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let width = area.x_end - area.x_start;
+    let height = area.y_end - area.y_start;
+    let columns = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+
+    let positions = match order {
+        TileOrder::Scanline => (0..rows).flat_map(|y| (0..columns).map(move |x| (x, y))).collect(),
+        TileOrder::Spiral => spiral(columns, rows),
+        TileOrder::Hilbert => hilbert(columns, rows)
+    };
+
+    return positions.into_iter().map(|(x, y)| Tile {
+        x_start: area.x_start + x * size,
+        x_end: area.x_start + ((x + 1) * size).min(width),
+        y_start: area.y_start + y * size,
+        y_end: area.y_start + ((y + 1) * size).min(height)
+    }).collect();
+}
+
+/* Walks a square spiral around the center tile, skipping the positions outside the grid */
+fn spiral(columns: u64, rows: u64) -> Vec<(u64, u64)> {
+    let count = columns * rows;
+    let mut positions = Vec::with_capacity(count as usize);
+    let (mut x, mut y) = (((columns as i64) - 1) / 2, ((rows as i64) - 1) / 2);
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut direction = 0;
+    let mut length = 1;
+
+    while (positions.len() as u64) < count {
+        // Every length is walked twice, once horizontally and once vertically
+        for _ in 0..2 {
+            let (dx, dy) = directions[direction];
+            for _ in 0..length {
+                if x >= 0 && y >= 0 && x < columns as i64 && y < rows as i64 {
+                    positions.push((x as u64, y as u64));
+                }
+                x += dx;
+                y += dy;
+            }
+            direction = (direction + 1) % 4;
+        }
+        length += 1;
+    }
+
+    return positions;
+}
+
+/* Follows a Hilbert curve over the smallest power of two square holding the grid */
+fn hilbert(columns: u64, rows: u64) -> Vec<(u64, u64)> {
+    let n = columns.max(rows).max(1).next_power_of_two();
+    let mut positions = Vec::with_capacity((columns * rows) as usize);
+
+    for d in 0..n * n {
+        // Converts the distance along the curve into a position (Wikipedia's d2xy)
+        let (mut x, mut y) = (0, 0);
+        let mut t = d;
+        let mut s = 1;
+        while s < n {
+            let rx = 1 & (t / 2);
+            let ry = 1 & (t ^ rx);
+            if ry == 0 {
+                if rx == 1 {
+                    x = s - 1 - x;
+                    y = s - 1 - y;
+                }
+                std::mem::swap(&mut x, &mut y);
+            }
+            x += s * rx;
+            y += s * ry;
+            t /= 4;
+            s *= 2;
+        }
+
+        if x < columns && y < rows {
+            positions.push((x, y));
+        }
+    }
+
+    return positions;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* Checks that the tiles cover every pixel of the area exactly once and nothing outside it */
+    fn assert_covers(area: Tile, size: u64, order: TileOrder) {
+        let width = area.x_end - area.x_start;
+        let height = area.y_end - area.y_start;
+        let mut covered = vec![0; (width * height) as usize];
+        for tile in tiles(&area, size, order) {
+            assert!(tile.x_start >= area.x_start && tile.x_end <= area.x_end && tile.x_start < tile.x_end);
+            assert!(tile.y_start >= area.y_start && tile.y_end <= area.y_end && tile.y_start < tile.y_end);
+            for y in tile.y_start..tile.y_end {
+                for x in tile.x_start..tile.x_end {
+                    covered[((y - area.y_start) * width + x - area.x_start) as usize] += 1;
+                }
+            }
+        }
+        assert!(covered.iter().all(|c| *c == 1));
+    }
+
+    #[test]
+    fn every_order_covers_the_area_once() {
+        let areas = [
+            Tile { x_start: 0, x_end: 400, y_start: 0, y_end: 200 },
+            Tile { x_start: 0, x_end: 33, y_start: 0, y_end: 97 },
+            Tile { x_start: 17, x_end: 90, y_start: 5, y_end: 6 },
+            Tile { x_start: 3, x_end: 4, y_start: 8, y_end: 9 }
+        ];
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            for area in areas {
+                for size in [1, 7, 32, 1000] {
+                    assert_covers(area, size, order);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_area_has_no_tiles() {
+        let area = Tile { x_start: 5, x_end: 5, y_start: 0, y_end: 10 };
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            assert!(tiles(&area, 8, order).is_empty());
+        }
+    }
+}
@@ -1,13 +1,53 @@
-use crate::structures::{scene::Scene};
+use crate::{aov::Aov, scenes::{SceneType, Sky}, filter::Filter, tiles::{Tile, TileOrder}, samplers::sampler::SamplerType, cryptomatte::{CryptomatteType, CryptomatteLayer}, structures::{scene::Scene}, media::media_info::ImageFormat, integrators::{integrator::{Integrator, IntegratorType}, path::BounceLimits}};
 
 pub struct GeneralInfo {
     pub out_filename: String,
     pub out_width: u64,
     pub out_height: u64,
     pub aa_sampling: u64,
+    pub unlimited_samples: bool,
     pub ray_recursion: u64,
     pub threads: u64,
-    pub animation: bool
+    pub animation: bool,
+    pub spectral: bool,
+    pub out_format: ImageFormat,
+    pub transparent: bool,
+    pub integrator: IntegratorType,
+    pub bounces: BounceLimits,
+    pub clamp: f32,
+    pub photons: u64,
+    pub photon_radius: f32,
+    pub photon_passes: u64,
+    pub aovs: Vec<Aov>,
+    pub cryptomatte: Vec<CryptomatteType>,
+    pub cryptomatte_ranks: u64,
+    pub denoise: bool,
+    pub sampler: SamplerType,
+    pub seed: u64,
+    pub adaptive_threshold: f32,
+    pub min_samples: u64,
+    pub filter: Filter,
+    pub tile_size: u64,
+    pub tile_order: TileOrder,
+    pub pass_samples: u64,
+    pub resume: bool,
+    pub time_limit: f32,
+    pub region: Option<Tile>,
+    pub crop: bool,
+    pub scene: SceneType,
+    pub sky: Option<Sky>,
+    pub sky_end: Option<Sky>,
+    pub voxels: Option<String>
+}
+
+impl GeneralInfo {
+    /** Photon maps to build, one per sample per pixel up to 16 unless set with --photon-passes */
+    pub fn photon_passes(&self) -> u64 {
+        if self.photon_passes > 0 {
+            return self.photon_passes;
+        }
+        return self.aa_sampling.min(16);
+    }
 }
 
 pub struct RenderInfo<'a> {
@@ -15,7 +55,12 @@ pub struct RenderInfo<'a> {
     pub width_end: u64,
     pub height_start: u64,
     pub height_end: u64,
+    pub sample_start: u64,
+    pub sample_end: u64,
     pub info: &'a GeneralInfo,
     pub scene: &'a Scene,
-    pub frame: u64
+    pub frame: u64,
+    pub integrator: &'a dyn Integrator,
+    pub aovs: &'a [Aov],
+    pub cryptomatte: &'a [CryptomatteLayer]
 }
\ No newline at end of file